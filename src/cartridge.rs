@@ -1,147 +1,246 @@
-use std::{fs::File, io::Read, vec};
+mod header;
+mod rom_only;
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod mbc7;
+mod eeprom;
+mod huc1;
+mod huc3;
+mod mmm01;
+mod wisdom_tree;
+mod sachen;
+mod bbd;
+mod camera;
+mod detect;
+mod mapper;
+mod loader;
+mod patch;
+mod error;
+mod rtc;
+
+pub use header::{CartridgeHeader, ChecksumMismatch};
+use header::{HEADER_END, ram_size_from_code};
+use rom_only::RomOnly;
+use mbc1::Mbc1;
+use mbc2::Mbc2;
+use mbc3::Mbc3;
+use mbc5::Mbc5;
+pub use mbc5::RumbleCallback;
+use mbc7::Mbc7;
+use huc1::HuC1;
+use huc3::HuC3;
+use mmm01::Mmm01;
+use wisdom_tree::WisdomTree;
+use sachen::Sachen;
+use bbd::{Bbd, BbdVariant};
+use camera::Camera;
+pub use mapper::{Mapper, MapperConstructor, register_mapper};
+use mapper::registered_mapper;
+pub use loader::{load_rom_file, load_patched_rom, extract_rom};
+pub use patch::{apply_patch, find_patch};
+pub use error::LoadError;
+pub use camera::{CameraImageProvider, png_image_provider};
+use detect::SpecialMapper;
+use crate::infrared::Infrared;
+pub use rtc::RtcClock;
+use rtc::{RTC_FOOTER_SIZE, RTC_FOOTER_SIZE_OLD};
 
-// Supports No MBC and MBC-1
 pub struct Cartridge{
-    mbc_type: u8, // Type of mbc
-    rom_size: u32,
-    ram_size: u32,
-    bank1_reg: u8,
-    bank2_reg: u8,
-    ram_bank: u8,
-    ram_enabled: bool,
-    rom_bitmask: u8, 
-    mbc_mode: u8, // Ram mode or Rom mode
-    storage: Vec<u8>,
-    //ext_ram: Vec<u8>
+    header: CartridgeHeader,
+    mapper: Box<dyn Mapper>,
+    ram_dirty: bool // Set when a write changes battery backed data, cleared once the save has been flushed
 }
 
 impl Cartridge{
-    pub fn new(file_name: &String) -> Cartridge{
-        let mut init_hdr =  [0; 0x014F]; // Temp structure to parse cartridge header
+    // Accepts .gb / .gbc files and .zip / .gz archives
+    pub fn new(file_name: &str) -> Result<Cartridge, LoadError>{
+        let storage = load_rom_file(file_name)?;
+        Cartridge::from_bytes(storage)
+    }
 
-        let mut f = File::open(file_name).expect("Unable to open rom file");
-        f.read(&mut init_hdr).expect("Error loading init_hdr");
+    pub fn from_bytes(mut storage: Vec<u8>) -> Result<Cartridge, LoadError>{
+        if storage.len() < HEADER_END{
+            return Err(LoadError::Truncated{ len: storage.len(), expected: HEADER_END });
+        }
 
-        f = File::open(file_name).expect("HAHA");
+        let header = CartridgeHeader::parse(&storage[..HEADER_END]);
 
-        let rom_size:u32 = (init_hdr[0x0148] + 1 ) as u32  * 0x7FFF + 1 ; // currently not supporting fractional MB
+        println!("Title: {}, Cartridge type: {:02X?}, Version: {}", header.title, header.cartridge_type, header.version);
 
-        println!("Size of external RAM: {} ", init_hdr[0x0149] );
+        for mismatch in header.verify_checksums(&storage){
+            match mismatch{
+                ChecksumMismatch::Header{expected, computed} => println!("Header checksum mismatch: expected {:02X?}, computed {:02X?}", expected, computed),
+                ChecksumMismatch::Global{expected, computed} => println!("Global checksum mismatch: expected {:04X?}, computed {:04X?}", expected, computed)
+            }
+        }
 
-        let mbc_type = match init_hdr[0x0147]{
-            0x00 => {0}
-            0x01..=0x03 => {1}
-            _ => {0}
+        // Homebrew and translated ROMs often disagree with their header, trust the file in that case
+        let rom_size = match header.rom_size(){
+            Some(size) if size == storage.len() => size,
+            declared => {
+                println!("ROM file is {} bytes but header declares {:?}, sizing from file", storage.len(), declared);
+                storage.len().max(0x8000)
+            }
         };
 
-        println!("Size of cartidge: {}", rom_size);
-
-        let mut cartridge: Cartridge = Cartridge{
-            mbc_type: mbc_type,
-            rom_size: rom_size,
-            ram_size: init_hdr[0x0149] as u32,
-            bank1_reg: 1,
-            bank2_reg: 0,
-            ram_bank: 0,
-            ram_enabled: false,
-            rom_bitmask: 0x3, // Need to set according to rom size
-            mbc_mode: 0,
-            storage: vec![0; rom_size as usize]
+        // Pad to a power of two banks so the bank mask mirrors the same way the hardware does
+        storage.resize(rom_size.next_power_of_two(), 0xFF);
+
+        let ram_size = match header.ram_size(){
+            Some(size) => size,
+            None => return Err(LoadError::BadHeader(format!("Unknown RAM size code: {:02X?}", header.ram_size_code)))
         };
-        
-        cartridge.load_rom(f);
 
-        cartridge
+        println!("Size of cartidge: {}, Size of external RAM: {}, Battery: {}", rom_size, ram_size, header.has_battery());
+
+        let ext_ram = vec![0; ram_size];
+
+        let mapper = Cartridge::create_mapper(&header, storage, ext_ram)?;
+
+        Ok(Cartridge{
+            header: header,
+            mapper: mapper,
+            ram_dirty: false
+        })
     }
 
-    pub fn load_rom(&mut self, mut file: File){
-        file.read(&mut self.storage).expect("Could not load rom into storage");
+    // Registered mappers first, then the ones that need more than the type byte to detect, then the type byte
+    fn create_mapper(header: &CartridgeHeader, storage: Vec<u8>, ext_ram: Vec<u8>) -> Result<Box<dyn Mapper>, LoadError>{
+        if let Some(constructor) = registered_mapper(header.cartridge_type){
+            println!("Using registered mapper for cartridge type {:02X?}", header.cartridge_type);
+            return Ok(constructor(storage, ext_ram));
+        }
+
+        let mapper: Box<dyn Mapper> = match detect::detect(header, &storage){
+            Some(SpecialMapper::Mmm01{ram_size_code}) => {
+                println!("Detected MMM01 multicart");
+                let ram = vec![0; ram_size_from_code(ram_size_code).unwrap_or(ext_ram.len())];
+                Box::new(Mmm01::new(storage, ram))
+            }
+            Some(SpecialMapper::WisdomTree) => {
+                println!("Detected Wisdom Tree cartridge");
+                Box::new(WisdomTree::new(storage))
+            }
+            Some(SpecialMapper::Sachen) => Box::new(Sachen::new(storage)),
+            Some(SpecialMapper::Bbd) => {
+                println!("Detected BBD cartridge");
+                Box::new(Bbd::new(storage, ext_ram, BbdVariant::Bbd))
+            }
+            Some(SpecialMapper::Hitek) => {
+                println!("Detected Hitek cartridge");
+                Box::new(Bbd::new(storage, ext_ram, BbdVariant::Hitek))
+            }
+            None => return Cartridge::mapper_from_type(header.cartridge_type, storage, ext_ram)
+        };
+        Ok(mapper)
     }
 
-    pub fn write_byte(&mut self, loc: u16, val: u8){
-        if self.mbc_type != 0 {
-
-            match loc{
-
-                0x0000..=0x1FFF => {
-                    println!("Writing to RAM enable register: {:2x?}", val);
-                    let lower_4_bits = val & 0x0F;
-                    if lower_4_bits == 0x0A{
-                        self.ram_enabled = true;
-                    }
-                    else{
-                        self.ram_enabled = false;
-                    }
-                    
-                }
+    fn mapper_from_type(cartridge_type: u8, storage: Vec<u8>, ext_ram: Vec<u8>) -> Result<Box<dyn Mapper>, LoadError>{
+        let mapper: Box<dyn Mapper> = match cartridge_type{
+            0x00 | 0x08 | 0x09 => Box::new(RomOnly::new(storage, ext_ram)),
+            0x01..=0x03 => Box::new(Mbc1::new(storage, ext_ram)),
+            0x05 | 0x06 => Box::new(Mbc2::new(storage)), // RAM is inside the mapper, header RAM size is 0
+            0x0F | 0x10 => Box::new(Mbc3::new(storage, ext_ram, true)),
+            0x11..=0x13 => Box::new(Mbc3::new(storage, ext_ram, false)),
+            0x19..=0x1B => Box::new(Mbc5::new(storage, ext_ram, false)),
+            0x1C..=0x1E => Box::new(Mbc5::new(storage, ext_ram, true)),
+            0x22 => Box::new(Mbc7::new(storage)), // EEPROM is inside the mapper
+            0xFC => Box::new(Camera::new(storage, ext_ram)),
+            0xFE => Box::new(HuC3::new(storage, ext_ram)),
+            0xFF => Box::new(HuC1::new(storage, ext_ram)),
+            _ => return Err(LoadError::UnsupportedMapper(cartridge_type))
+        };
+        Ok(mapper)
+    }
 
-                0x2000..=0x3FFF => {
-                    println!("VAL: {}", val);
-                    let low_5_bits = match val & 0x1F{ // Check the +1 logic
-                        0x0 => {
-                            println!("Bank numbers last 5 bits is zero");
-                            1
-                        }
-                        _ => val & 0x1F
-                    };
-                    self.bank1_reg = low_5_bits ;
-                    println!(" Low 5 bits: {:02X? },  Bank1_Reg set to: {:02X?}", low_5_bits, self.bank1_reg);
-                }
+    pub fn set_rtc_clock(&mut self, clock: RtcClock){
+        self.mapper.set_rtc_clock(clock);
+    }
 
-                0x4000..=0x5FFF => {
-                    // If ROM mode - Change upper 2 bits of rom_bank
-                    if self.mbc_mode == 0{
-                        self.bank2_reg = val & 0x3;
-                    }
-                    else{
-                        println!("Maybe need to impl RAM banking");
-                    }
-                }
+    // Lets the frontend follow the rumble motor of MBC5 rumble carts
+    pub fn set_rumble_callback(&mut self, callback: RumbleCallback){
+        self.mapper.set_rumble_callback(callback);
+    }
 
-                0x6000..=0x8000 => {
-                    println!("Changing modes");
-                    if val & 0x01 == 0 {
-                        self.mbc_mode = 0;
-                    }
-                    else{
-                        self.mbc_mode = 1;
-                    }
-                }
+    // Feeds the MBC7 accelerometer, x and y are in g
+    pub fn set_tilt(&mut self, x: f32, y: f32){
+        self.mapper.set_tilt(x, y);
+    }
 
-                _ => {
-                    println!("Writing to another men regioon at loc: {:04X?}", loc);
-                    self.storage[loc as usize] = val;
-                }
+    // Connects the IR transceiver of HuC1 / HuC3 carts
+    pub fn set_infrared(&mut self, infrared: Box<dyn Infrared>){
+        self.mapper.set_infrared(infrared);
+    }
 
-            }
-        }
-        else{
-            println!("Writing to cartridge when no MBC is present");
+    // Supplies the image seen by the Pocket Camera sensor
+    pub fn set_camera_image_provider(&mut self, provider: CameraImageProvider){
+        self.mapper.set_camera_image_provider(provider);
+    }
+
+    pub fn has_battery(&self) -> bool{
+        self.header.has_battery()
+    }
+
+    // External RAM ( or EEPROM ) contents followed by the RTC footer on clock carts, as stored in the .sav file
+    pub fn save_data(&self) -> Vec<u8>{
+        let mut data = self.mapper.save_data().to_vec();
+        if let Some(footer) = self.mapper.rtc_footer(){
+            data.extend_from_slice(&footer);
         }
+        data
     }
 
-    pub fn read_byte(&self, loc: u16) -> u8{
+    // Saves from other emulators may be shorter or longer than the RAM, copy what fits
+    pub fn load_save_data(&mut self, data: &[u8]){
+        let ram = self.mapper.save_data_mut();
+        let len = ram.len().min(data.len());
+        ram[..len].copy_from_slice(&data[..len]);
 
-        if self.mbc_type == 0{
-            self.storage[loc as usize]
+        let footer = &data[len..];
+        if footer.len() == RTC_FOOTER_SIZE || footer.len() == RTC_FOOTER_SIZE_OLD{
+            self.mapper.load_rtc_footer(footer);
         }
-        else{// Mbc type 1
-            match loc{
-                0x0000..=0x3FFF => {  self.storage[loc as usize] }
-                0x4000..=0x7FFF => {
-                    let rom_bank = ((self.bank2_reg << 5) | self.bank1_reg) & self.rom_bitmask;
-                    let rom_offset: u32 =(rom_bank  )as u32 * 0x4000 ;
-                    //println!("ROM BANK: {}, loc: {:04X?}", rom_bank, loc);
-                    //let addr: usize = (rom_offset + (loc - 0x4000) as u32) as usize;
-                    //println!("Addr: {:04X?}", addr);
-                    self.storage[ (rom_offset + (loc - 0x4000) as u32) as usize]
+    }
+
+    // Returns whether external RAM changed since the last call
+    pub fn take_ram_dirty(&mut self) -> bool{
+        let dirty = self.ram_dirty;
+        self.ram_dirty = false;
+        dirty
+    }
+
+    pub fn step(&mut self, m_cycles: u8){
+        self.mapper.step(m_cycles);
+    }
+
+    pub fn write_byte(&mut self, loc: u16, val: u8){
+        match loc{
+            0x0000..=0x7FFF => {
+                self.mapper.write_rom(loc, val);
+            }
+            0xA000..=0xBFFF => {
+                if self.mapper.write_ram(loc, val){
+                    self.ram_dirty = true;
                 }
-                _ => { 1 }        
+            }
+            _ => {
+                println!("Writing to non cartridge memory at loc: {:04X?}", loc);
             }
         }
-        
     }
 
-}
+    pub fn read_byte(&self, loc: u16) -> u8{
+        match loc{
+            0x0000..=0x7FFF => {
+                self.mapper.read_rom(loc)
+            }
+            0xA000..=0xBFFF => {
+                self.mapper.read_ram(loc)
+            }
+            _ => { 0xFF }
+        }
+    }
 
-    
+}
@@ -0,0 +1,125 @@
+use super::mbc5::Mbc5;
+use super::mapper::Mapper;
+
+// BBD and Hitek - MBC5 clones that scramble the data lines of 0x4000 - 0x7FFF and the bits of the ROM bank number.
+// 0x2001 selects the data permutation, 0x2080 the bank permutation. Tables as documented by mGBA.
+#[derive(Clone, Copy, PartialEq)]
+pub enum BbdVariant{
+    Bbd,
+    Hitek
+}
+
+const BBD_DATA_ORDER: [[u8; 8]; 8] = [
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [0, 5, 1, 3, 4, 2, 6, 7],
+    [0, 4, 2, 3, 1, 5, 6, 7],
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [0, 1, 5, 3, 4, 2, 6, 7]
+];
+
+const BBD_BANK_ORDER: [[u8; 8]; 8] = [
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [3, 4, 2, 0, 1, 5, 6, 7],
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [1, 2, 3, 4, 0, 5, 6, 7],
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [0, 1, 2, 3, 4, 5, 6, 7]
+];
+
+const HITEK_DATA_ORDER: [[u8; 8]; 8] = [
+    [7, 6, 5, 4, 3, 2, 1, 0],
+    [0, 6, 5, 3, 4, 1, 2, 7],
+    [0, 5, 6, 3, 4, 2, 1, 7],
+    [0, 6, 2, 3, 4, 5, 1, 7],
+    [0, 6, 1, 3, 4, 5, 2, 7],
+    [0, 1, 6, 3, 4, 5, 2, 7],
+    [0, 2, 6, 3, 4, 1, 5, 7],
+    [0, 6, 2, 3, 4, 1, 5, 7]
+];
+
+const HITEK_BANK_ORDER: [[u8; 8]; 8] = [
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [3, 2, 1, 0, 4, 5, 6, 7],
+    [2, 1, 0, 3, 4, 5, 6, 7],
+    [1, 0, 3, 2, 4, 5, 6, 7],
+    [0, 3, 2, 1, 4, 5, 6, 7],
+    [2, 3, 0, 1, 4, 5, 6, 7],
+    [3, 0, 1, 2, 4, 5, 6, 7],
+    [2, 0, 1, 3, 4, 5, 6, 7]
+];
+
+// Bit i of the result is bit order[i] of the input
+fn reorder_bits(val: u8, order: &[u8; 8]) -> u8{
+    let mut result = 0;
+    for (new_bit, old_bit) in order.iter().enumerate(){
+        result |= ((val >> old_bit) & 1) << new_bit;
+    }
+    result
+}
+
+pub struct Bbd{
+    mbc5: Mbc5,
+    data_order: &'static [[u8; 8]; 8],
+    bank_order: &'static [[u8; 8]; 8],
+    data_mode: u8,
+    bank_mode: u8
+}
+
+impl Bbd{
+
+    pub fn new(rom: Vec<u8>, ram: Vec<u8>, variant: BbdVariant) -> Bbd{
+        let (data_order, bank_order) = match variant{
+            BbdVariant::Bbd => (&BBD_DATA_ORDER, &BBD_BANK_ORDER),
+            BbdVariant::Hitek => (&HITEK_DATA_ORDER, &HITEK_BANK_ORDER)
+        };
+
+        Bbd{
+            mbc5: Mbc5::new(rom, ram, false),
+            data_order: data_order,
+            bank_order: bank_order,
+            data_mode: 0,
+            bank_mode: 0
+        }
+    }
+}
+
+impl Mapper for Bbd{
+
+    fn save_data(&self) -> &[u8]{
+        self.mbc5.save_data()
+    }
+
+    fn save_data_mut(&mut self) -> &mut [u8]{
+        self.mbc5.save_data_mut()
+    }
+
+    fn read_rom(&self, loc: u16) -> u8{
+        let val = self.mbc5.read_rom(loc);
+        match loc{
+            0x0000..=0x3FFF => val,
+            _ => reorder_bits(val, &self.data_order[self.data_mode as usize])
+        }
+    }
+
+    fn write_rom(&mut self, loc: u16, val: u8){
+        match loc & 0xF0FF{
+            0x2000 => self.mbc5.write_rom(loc, reorder_bits(val, &self.bank_order[self.bank_mode as usize])),
+            0x2001 => self.data_mode = val & 0x07,
+            0x2080 => self.bank_mode = val & 0x07,
+            _ => self.mbc5.write_rom(loc, val)
+        }
+    }
+
+    fn read_ram(&self, loc: u16) -> u8{
+        self.mbc5.read_ram(loc)
+    }
+
+    fn write_ram(&mut self, loc: u16, val: u8) -> bool{
+        self.mbc5.write_ram(loc, val)
+    }
+}
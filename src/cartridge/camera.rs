@@ -0,0 +1,248 @@
+use std::{fs::File, io};
+
+use super::mapper::{store, Mapper};
+
+pub const CAMERA_WIDTH: usize = 128;
+pub const CAMERA_HEIGHT: usize = 112;
+
+// Fills a CAMERA_WIDTH x CAMERA_HEIGHT buffer with the light hitting the sensor, 0 - dark, 255 - bright
+pub type CameraImageProvider = Box<dyn FnMut(&mut [u8])>;
+
+const REG_CAPTURE: usize = 0x00;     // Bit 0 - start / busy, bits 1-2 - output select
+const REG_GAIN_EDGE: usize = 0x01;   // Bit 7 - N, bits 5-6 - VH edge direction, bits 0-4 - gain
+const REG_EXPOSURE_HI: usize = 0x02;
+const REG_EXPOSURE_LO: usize = 0x03;
+const REG_EDGE_INVERT: usize = 0x04; // Bits 4-6 - edge ratio, bit 3 - invert, bits 0-2 - reference voltage
+const REG_DITHER_START: usize = 0x06; // 4x4 matrix of 3 thresholds, up to 0x35
+const REG_COUNT: usize = 0x36;
+
+const IMAGE_RAM_OFFSET: usize = 0x0100; // Captured tiles land at 0xA100 in RAM bank 0
+
+const EDGE_RATIOS: [f32; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+
+// Loads a PNG as the sensor image, scaled to the sensor resolution and converted to luminance
+pub fn png_image_provider(path: &str) -> io::Result<CameraImageProvider>{
+    let invalid = |e: png::DecodingError| io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (info, mut reader) = decoder.read_info().map_err(invalid)?;
+    let mut buf = vec![0; info.buffer_size()];
+    reader.next_frame(&mut buf).map_err(invalid)?;
+
+    let channels = match info.color_type{
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::RGB => 3,
+        _ => 4
+    };
+
+    let mut image = vec![0; CAMERA_WIDTH * CAMERA_HEIGHT];
+    for y in 0..CAMERA_HEIGHT{
+        for x in 0..CAMERA_WIDTH{
+            // Nearest neighbour scaling
+            let src_x = x * info.width as usize / CAMERA_WIDTH;
+            let src_y = y * info.height as usize / CAMERA_HEIGHT;
+            let px = &buf[(src_y * info.width as usize + src_x) * channels..];
+            image[y * CAMERA_WIDTH + x] = if channels >= 3{
+                ((px[0] as u32 * 299 + px[1] as u32 * 587 + px[2] as u32 * 114) / 1000) as u8
+            } else{
+                px[0]
+            };
+        }
+    }
+
+    Ok(Box::new(move |sensor: &mut [u8]| sensor.copy_from_slice(&image)))
+}
+
+// Pocket Camera - MBC with 128 KiB RAM and a Mitsubishi M64282FP image sensor
+pub struct Camera{
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rom_bank: u8,
+    ram_bank: u8,
+    ram_write_enabled: bool, // Reads always work
+    registers_mapped: bool,  // Bit 4 of 0x4000 - 0x5FFF maps the sensor registers over RAM
+    registers: [u8; REG_COUNT],
+    capture_cycles: u32,     // M-cycles left until the capture in progress completes
+    rom_bitmask: u8,
+    image_provider: Option<CameraImageProvider>
+}
+
+impl Camera{
+
+    pub fn new(rom: Vec<u8>, ram: Vec<u8>) -> Camera{
+        let rom_bitmask = ((rom.len() / 0x4000 - 1) & 0x3F) as u8;
+
+        Camera{
+            rom: rom,
+            ram: ram,
+            rom_bank: 1,
+            ram_bank: 0,
+            ram_write_enabled: false,
+            registers_mapped: false,
+            registers: [0; REG_COUNT],
+            capture_cycles: 0,
+            rom_bitmask: rom_bitmask,
+            image_provider: None
+        }
+    }
+
+    fn exposure(&self) -> u32{
+        ((self.registers[REG_EXPOSURE_HI] as u32) << 8) | self.registers[REG_EXPOSURE_LO] as u32
+    }
+
+    fn start_capture(&mut self){
+        // Readout of the whole sensor plus the exposure time, in M-cycles
+        let n_bit = (self.registers[REG_GAIN_EDGE] & 0x80) != 0;
+        self.capture_cycles = 32448 + if n_bit {0} else {512} + self.exposure() * 16;
+    }
+
+    // Runs the sensor pipeline ( gain, edge enhancement, exposure, inversion, dithering ) and stores the result as tiles
+    fn capture(&mut self){
+        let mut sensor = vec![0x80; CAMERA_WIDTH * CAMERA_HEIGHT];
+        if let Some(provider) = &mut self.image_provider{
+            provider(&mut sensor);
+        }
+
+        let gain = 0.88 + (self.registers[REG_GAIN_EDGE] & 0x1F) as f32 * (1.06 / 31.0);
+        let light = |x: i32, y: i32| -> f32{
+            let x = x.max(0).min(CAMERA_WIDTH as i32 - 1) as usize;
+            let y = y.max(0).min(CAMERA_HEIGHT as i32 - 1) as usize;
+            sensor[y * CAMERA_WIDTH + x] as f32 * gain
+        };
+
+        let edge_ratio = EDGE_RATIOS[((self.registers[REG_EDGE_INVERT] >> 4) & 0x07) as usize];
+        let edge_mode = (self.registers[REG_GAIN_EDGE] >> 5) & 0x03; // 1 - horizontal, 2 - vertical, 3 - both
+        let invert = (self.registers[REG_EDGE_INVERT] & 0x08) != 0;
+        let exposure = self.exposure() as f32 / 0x1000 as f32;
+
+        for y in 0..CAMERA_HEIGHT{
+            for x in 0..CAMERA_WIDTH{
+                let (xi, yi) = (x as i32, y as i32);
+                let mut value = light(xi, yi);
+
+                if (edge_mode & 0x01) != 0{
+                    value += edge_ratio * (2.0 * light(xi, yi) - light(xi - 1, yi) - light(xi + 1, yi));
+                }
+                if (edge_mode & 0x02) != 0{
+                    value += edge_ratio * (2.0 * light(xi, yi) - light(xi, yi - 1) - light(xi, yi + 1));
+                }
+
+                value = (value * exposure).max(0.0).min(255.0);
+                if invert{
+                    value = 255.0 - value;
+                }
+
+                // Each pixel of the 4x4 matrix has its own 3 thresholds splitting the range into 4 shades
+                let matrix = REG_DITHER_START + ((y & 3) * 4 + (x & 3)) * 3;
+                let value = value as u8;
+                let shade = if value < self.registers[matrix]{
+                    3
+                } else if value < self.registers[matrix + 1]{
+                    2
+                } else if value < self.registers[matrix + 2]{
+                    1
+                } else{
+                    0
+                };
+
+                let tile = (y / 8) * (CAMERA_WIDTH / 8) + x / 8;
+                let row = IMAGE_RAM_OFFSET + tile * 16 + (y % 8) * 2;
+                let bit = 0x80 >> (x % 8);
+                if self.ram.len() <= row + 1{
+                    continue;
+                }
+                self.ram[row] = if (shade & 0x01) != 0 {self.ram[row] | bit} else {self.ram[row] & !bit};
+                self.ram[row + 1] = if (shade & 0x02) != 0 {self.ram[row + 1] | bit} else {self.ram[row + 1] & !bit};
+            }
+        }
+    }
+
+    fn ram_offset(&self, loc: u16) -> usize{
+        (self.ram_bank as usize * 0x2000 + (loc - 0xA000) as usize) % self.ram.len()
+    }
+}
+
+impl Mapper for Camera{
+
+    fn set_camera_image_provider(&mut self, provider: CameraImageProvider){
+        self.image_provider = Some(provider);
+    }
+
+    fn step(&mut self, m_cycles: u8){
+        if self.capture_cycles == 0{
+            return;
+        }
+        self.capture_cycles = self.capture_cycles.saturating_sub(m_cycles as u32);
+        if self.capture_cycles == 0{
+            self.capture();
+            self.registers[REG_CAPTURE] &= !0x01;
+        }
+    }
+
+    fn save_data(&self) -> &[u8]{
+        &self.ram
+    }
+
+    fn save_data_mut(&mut self) -> &mut [u8]{
+        &mut self.ram
+    }
+
+    fn read_rom(&self, loc: u16) -> u8{
+        match loc{
+            0x0000..=0x3FFF => self.rom[loc as usize],
+            _ => {
+                // Bank 0 can be mapped to 0x4000 - 0x7FFF
+                let rom_bank = (self.rom_bank & self.rom_bitmask) as usize;
+                self.rom[rom_bank * 0x4000 + (loc - 0x4000) as usize]
+            }
+        }
+    }
+
+    fn write_rom(&mut self, loc: u16, val: u8){
+        match loc{
+            0x0000..=0x1FFF => self.ram_write_enabled = (val & 0x0F) == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = val & 0x3F,
+            0x4000..=0x5FFF => {
+                self.registers_mapped = (val & 0x10) != 0;
+                self.ram_bank = val & 0x0F;
+            }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, loc: u16) -> u8{
+        if self.registers_mapped{
+            // Only the capture register can be read back, mirrored every 0x80 bytes
+            return if (loc & 0x7F) == 0 {self.registers[REG_CAPTURE]} else {0x00};
+        }
+        if self.ram.is_empty(){
+            return 0xFF;
+        }
+        self.ram[self.ram_offset(loc)]
+    }
+
+    fn write_ram(&mut self, loc: u16, val: u8) -> bool{
+        if self.registers_mapped{
+            let reg = (loc & 0x7F) as usize;
+            if reg == REG_CAPTURE{
+                let start = (val & 0x01) != 0 && (self.registers[REG_CAPTURE] & 0x01) == 0;
+                self.registers[REG_CAPTURE] = (self.registers[REG_CAPTURE] & 0x01) | (val & 0x06);
+                if start{
+                    self.registers[REG_CAPTURE] |= 0x01;
+                    self.start_capture();
+                }
+            }
+            else if reg < REG_COUNT{
+                self.registers[reg] = val;
+            }
+            return false;
+        }
+        if !self.ram_write_enabled || self.ram.is_empty(){
+            return false;
+        }
+        let offset = self.ram_offset(loc);
+        store(&mut self.ram, offset, val)
+    }
+}
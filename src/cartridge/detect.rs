@@ -0,0 +1,60 @@
+use super::header::{is_valid_header, CartridgeHeader, CARTRIDGE_TYPE, RAM_SIZE};
+use super::sachen::has_scrambled_logo;
+use crate::util::crc32;
+
+// Mappers that can not be picked from the cartridge type byte alone
+pub enum SpecialMapper{
+    Mmm01{ ram_size_code: u8 },
+    WisdomTree,
+    Sachen,
+    Bbd,
+    Hitek
+}
+
+// CRC-32 of the secondary logo at 0x0184 - 0x01B3, as catalogued by mGBA
+const HITEK_LOGO_CRC: u32 = 0x4FDAB691;
+const BBD_LOGO_CRCS: [u32; 2] = [0xC7D8C1DF, 0x6D1EA662];
+
+fn is_mmm01_type(cartridge_type: u8) -> bool{
+    (0x0B..=0x0D).contains(&cartridge_type)
+}
+
+fn has_wisdom_tree_name(rom: &[u8]) -> bool{
+    rom.windows(11).any(|w| w == b"WISDOM TREE" || w == b"WISDOM\0TREE")
+}
+
+pub fn detect(header: &CartridgeHeader, rom: &[u8]) -> Option<SpecialMapper>{
+    // MMM01 menus live in the last 32 KiB, the header at 0x0100 usually belongs to the first game.
+    // The menu has to boot on its own, so a bare type byte there is not enough
+    if rom.len() >= 0x8000{
+        let menu_header = rom.len() - 0x8000;
+        if is_mmm01_type(rom[menu_header + CARTRIDGE_TYPE]) && is_valid_header(&rom[menu_header..]){
+            return Some(SpecialMapper::Mmm01{ ram_size_code: rom[menu_header + RAM_SIZE] });
+        }
+    }
+    if is_mmm01_type(header.cartridge_type){
+        return Some(SpecialMapper::Mmm01{ ram_size_code: header.ram_size_code });
+    }
+
+    if has_scrambled_logo(rom){
+        let model = if (header.cgb_flag & 0x80) != 0 {"MMC2"} else {"MMC1"};
+        println!("Detected Sachen {} cartridge", model);
+        return Some(SpecialMapper::Sachen);
+    }
+
+    let secondary_logo = crc32(&rom[0x0184..0x01B4]);
+    if secondary_logo == HITEK_LOGO_CRC{
+        return Some(SpecialMapper::Hitek);
+    }
+    // Fixed dumps with the scrambling undone set 0x7FFF to 0x01
+    if BBD_LOGO_CRCS.contains(&secondary_logo) && rom[0x7FFF] != 0x01{
+        return Some(SpecialMapper::Bbd);
+    }
+
+    // Most Wisdom Tree games claim to be ROM only, they are recognised by the company name in the ROM
+    if header.cartridge_type == 0xC0 || (header.cartridge_type == 0x00 && rom.len() > 0x8000 && has_wisdom_tree_name(rom)){
+        return Some(SpecialMapper::WisdomTree);
+    }
+
+    None
+}
@@ -0,0 +1,187 @@
+const EEPROM_SIZE: usize = 256;
+
+enum EepromState{
+    Idle,    // Waiting for the start bit
+    Command, // Shifting in 2 opcode bits + 8 address bits
+    Read,    // Shifting out 16 bit words
+    Write,   // Shifting in a 16 bit word
+    Done     // Operation finished, waiting for CS to drop
+}
+
+// 93LC56 serial EEPROM in 16 bit organisation ( 128 words ) as used by MBC7
+pub struct Eeprom{
+    data: Vec<u8>, // Words stored little endian
+    cs: bool,
+    clk: bool,
+    di: bool,
+    do_bit: bool,
+    write_enabled: bool,
+    write_all: bool,
+    state: EepromState,
+    shift: u16,
+    bits: u8,
+    address: u8,
+    modified: bool // Data changed during the current write()
+}
+
+impl Eeprom{
+
+    pub fn new() -> Eeprom{
+        Eeprom{
+            data: vec![0xFF; EEPROM_SIZE],
+            cs: false,
+            clk: false,
+            di: false,
+            do_bit: true,
+            write_enabled: false,
+            write_all: false,
+            state: EepromState::Idle,
+            shift: 0,
+            bits: 0,
+            address: 0,
+            modified: false
+        }
+    }
+
+    pub fn data(&self) -> &[u8]{
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8]{
+        &mut self.data
+    }
+
+    fn word(&self, address: u8) -> u16{
+        let idx = (address & 0x7F) as usize * 2;
+        self.data[idx] as u16 | ((self.data[idx + 1] as u16) << 8)
+    }
+
+    fn set_word(&mut self, address: u8, val: u16){
+        let idx = (address & 0x7F) as usize * 2;
+        self.modified |= self.word(address) != val;
+        self.data[idx] = val as u8;
+        self.data[idx + 1] = (val >> 8) as u8;
+    }
+
+    // Bit 7 - CS, Bit 6 - CLK, Bit 1 - DI, Bit 0 - DO
+    pub fn read(&self) -> u8{
+        ((self.cs as u8) << 7) | ((self.clk as u8) << 6) | ((self.di as u8) << 1) | (self.do_bit as u8)
+    }
+
+    // Returns true if a write or erase command changed the stored data
+    pub fn write(&mut self, val: u8) -> bool{
+        self.modified = false;
+        let cs = (val & 0x80) != 0;
+        let clk = (val & 0x40) != 0;
+        let di = (val & 0x02) != 0;
+
+        if !cs{
+            // Deselecting aborts whatever was in progress, DO goes back to ready
+            self.state = EepromState::Idle;
+            self.do_bit = true;
+        }
+        else if clk && !self.clk{
+            self.clock_in(di);
+        }
+
+        self.cs = cs;
+        self.clk = clk;
+        self.di = di;
+        self.modified
+    }
+
+    fn clock_in(&mut self, di: bool){
+        match self.state{
+            EepromState::Idle => {
+                if di{
+                    self.state = EepromState::Command;
+                    self.shift = 0;
+                    self.bits = 0;
+                }
+            }
+            EepromState::Command => {
+                self.shift = (self.shift << 1) | di as u16;
+                self.bits += 1;
+                if self.bits == 10{
+                    self.execute();
+                }
+            }
+            EepromState::Read => {
+                self.do_bit = (self.shift >> (15 - self.bits)) & 0x01 != 0;
+                self.bits += 1;
+                if self.bits == 16{
+                    // Sequential read carries on into the next word
+                    self.address = self.address.wrapping_add(1) & 0x7F;
+                    self.shift = self.word(self.address);
+                    self.bits = 0;
+                }
+            }
+            EepromState::Write => {
+                self.shift = (self.shift << 1) | di as u16;
+                self.bits += 1;
+                if self.bits == 16{
+                    if self.write_enabled{
+                        if self.write_all{
+                            for address in 0..0x80{
+                                self.set_word(address, self.shift);
+                            }
+                        }
+                        else{
+                            self.set_word(self.address, self.shift);
+                        }
+                    }
+                    self.do_bit = true;
+                    self.state = EepromState::Done;
+                }
+            }
+            EepromState::Done => {}
+        }
+    }
+
+    fn execute(&mut self){
+        let opcode = (self.shift >> 8) & 0x03;
+        let address = (self.shift & 0xFF) as u8;
+        self.address = address & 0x7F;
+        self.bits = 0;
+
+        match opcode{
+            0b10 => { // READ, a dummy 0 comes out before the data
+                self.shift = self.word(self.address);
+                self.do_bit = false;
+                self.state = EepromState::Read;
+            }
+            0b01 => { // WRITE
+                self.shift = 0;
+                self.write_all = false;
+                self.state = EepromState::Write;
+            }
+            0b11 => { // ERASE
+                if self.write_enabled{
+                    self.set_word(self.address, 0xFFFF);
+                }
+                self.do_bit = true;
+                self.state = EepromState::Done;
+            }
+            _ => {
+                match address >> 6{
+                    0b11 => { self.write_enabled = true; self.state = EepromState::Done; }  // EWEN
+                    0b00 => { self.write_enabled = false; self.state = EepromState::Done; } // EWDS
+                    0b10 => { // ERAL
+                        if self.write_enabled{
+                            for address in 0..0x80{
+                                self.set_word(address, 0xFFFF);
+                            }
+                        }
+                        self.do_bit = true;
+                        self.state = EepromState::Done;
+                    }
+                    _ => { // WRAL
+                        self.shift = 0;
+                        self.write_all = true;
+                        self.state = EepromState::Write;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::{error::Error, fmt, io, path::{Path, PathBuf}};
+
+#[derive(Debug)]
+pub enum LoadError{
+    NotFound(PathBuf),
+    Truncated{ len: usize, expected: usize }, // File too short to hold what the header needs
+    UnsupportedMapper(u8),                    // Cartridge type byte
+    BadHeader(String),
+    BadPatch{ path: PathBuf, reason: String },
+    Io(io::Error)                             // Any other read or archive error
+}
+
+impl LoadError{
+
+    // Tells a missing file apart from other IO errors
+    pub fn from_io(err: io::Error, path: &Path) -> LoadError{
+        match err.kind(){
+            io::ErrorKind::NotFound => LoadError::NotFound(path.to_path_buf()),
+            _ => LoadError::Io(err)
+        }
+    }
+}
+
+impl fmt::Display for LoadError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            LoadError::NotFound(path) => write!(f, "ROM file not found: {}", path.display()),
+            LoadError::Truncated{len, expected} => write!(f, "ROM is truncated: {} bytes, needs at least {}", len, expected),
+            LoadError::UnsupportedMapper(cartridge_type) => write!(f, "Unsupported cartridge type: {:02X?}", cartridge_type),
+            LoadError::BadHeader(reason) => write!(f, "Bad cartridge header: {}", reason),
+            LoadError::BadPatch{path, reason} => write!(f, "Could not apply patch {}: {}", path.display(), reason),
+            LoadError::Io(err) => write!(f, "Could not read ROM: {}", err)
+        }
+    }
+}
+
+impl Error for LoadError{}
+
+impl From<io::Error> for LoadError{
+    fn from(err: io::Error) -> LoadError{
+        LoadError::Io(err)
+    }
+}
@@ -0,0 +1,153 @@
+// Cartridge header: 0x0100 - 0x014F
+pub const HEADER_END: usize = 0x0150;
+
+pub const LOGO_START: usize = 0x0104;
+pub const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E
+];
+
+const TITLE_START: usize = 0x0134;
+const MANUFACTURER_START: usize = 0x013F;
+const CGB_FLAG: usize = 0x0143;
+const NEW_LICENSEE_START: usize = 0x0144;
+const SGB_FLAG: usize = 0x0146;
+pub const CARTRIDGE_TYPE: usize = 0x0147;
+const ROM_SIZE: usize = 0x0148;
+pub const RAM_SIZE: usize = 0x0149;
+const OLD_LICENSEE: usize = 0x014B;
+const VERSION: usize = 0x014C;
+const HEADER_CHECKSUM: usize = 0x014D;
+const GLOBAL_CHECKSUM: usize = 0x014E; // Big endian, 2 bytes
+
+pub enum ChecksumMismatch{
+    Header{ expected: u8, computed: u8 },
+    Global{ expected: u16, computed: u16 }
+}
+
+pub struct CartridgeHeader{
+    pub title: String,
+    pub manufacturer_code: Option<String>, // Only present on later carts, overlaps the end of the title
+    pub cgb_flag: u8,         // 0x80 - CGB enhanced, 0xC0 - CGB only
+    pub sgb_flag: u8,         // 0x03 - SGB functions supported
+    pub cartridge_type: u8,
+    pub rom_size_code: u8,
+    pub ram_size_code: u8,
+    pub old_licensee: u8,
+    pub new_licensee: String, // Only used when old_licensee is 0x33
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16
+}
+
+impl CartridgeHeader{
+
+    // Expects at least HEADER_END bytes
+    pub fn parse(rom: &[u8]) -> CartridgeHeader{
+        let cgb_flag = rom[CGB_FLAG];
+
+        // CGB era carts shrink the title to 11 bytes and store a 4 char manufacturer code after it
+        let manufacturer = &rom[MANUFACTURER_START..CGB_FLAG];
+        let has_manufacturer = (cgb_flag & 0x80) != 0 && manufacturer.iter().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit());
+
+        let title_end = if has_manufacturer{
+            MANUFACTURER_START
+        } else if (cgb_flag & 0x80) != 0{
+            CGB_FLAG
+        } else{
+            CGB_FLAG + 1
+        };
+
+        CartridgeHeader{
+            title: ascii_string(&rom[TITLE_START..title_end]),
+            manufacturer_code: if has_manufacturer { Some(ascii_string(manufacturer)) } else { None },
+            cgb_flag,
+            sgb_flag: rom[SGB_FLAG],
+            cartridge_type: rom[CARTRIDGE_TYPE],
+            rom_size_code: rom[ROM_SIZE],
+            ram_size_code: rom[RAM_SIZE],
+            old_licensee: rom[OLD_LICENSEE],
+            new_licensee: ascii_string(&rom[NEW_LICENSEE_START..SGB_FLAG]),
+            version: rom[VERSION],
+            header_checksum: rom[HEADER_CHECKSUM],
+            global_checksum: ((rom[GLOBAL_CHECKSUM] as u16) << 8) | rom[GLOBAL_CHECKSUM + 1] as u16
+        }
+    }
+
+    // Size of the ROM in bytes, None for unknown codes
+    pub fn rom_size(&self) -> Option<usize>{
+        match self.rom_size_code{
+            0x00..=0x08 => Some(0x8000 << self.rom_size_code),
+            0x52 => Some(72 * 0x4000),
+            0x53 => Some(80 * 0x4000),
+            0x54 => Some(96 * 0x4000),
+            _ => None
+        }
+    }
+
+    // Size of the external RAM in bytes, None for unknown codes
+    pub fn ram_size(&self) -> Option<usize>{
+        ram_size_from_code(self.ram_size_code)
+    }
+
+    // Cartridge types that keep their RAM ( or mapper RAM / EEPROM ) alive with a battery
+    pub fn has_battery(&self) -> bool{
+        match self.cartridge_type{
+            0x03 | 0x06 | 0x09 | 0x0D | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E | 0x22 | 0xFC | 0xFE | 0xFF => true,
+            _ => false
+        }
+    }
+
+    pub fn verify_checksums(&self, rom: &[u8]) -> Vec<ChecksumMismatch>{
+        let mut mismatches = Vec::new();
+
+        let header_computed = compute_header_checksum(rom);
+        if header_computed != self.header_checksum{
+            mismatches.push(ChecksumMismatch::Header{ expected: self.header_checksum, computed: header_computed });
+        }
+
+        // Sum of every byte in the ROM except the two checksum bytes themselves
+        let global_computed = rom.iter().enumerate()
+            .filter(|(i, _)| *i != GLOBAL_CHECKSUM && *i != GLOBAL_CHECKSUM + 1)
+            .fold(0u16, |acc, (_, b)| acc.wrapping_add(*b as u16));
+        if global_computed != self.global_checksum{
+            mismatches.push(ChecksumMismatch::Global{ expected: self.global_checksum, computed: global_computed });
+        }
+
+        mismatches
+    }
+}
+
+// rom starts at the bank holding the header, the checksum covers 0x0134 - 0x014C
+pub fn compute_header_checksum(rom: &[u8]) -> u8{
+    rom[TITLE_START..HEADER_CHECKSUM].iter().fold(0u8, |acc, b| acc.wrapping_sub(*b).wrapping_sub(1))
+}
+
+// What the boot ROM checks before running a cartridge
+pub fn is_valid_header(rom: &[u8]) -> bool{
+    rom.len() >= HEADER_END
+        && rom[LOGO_START..LOGO_START + 48] == NINTENDO_LOGO
+        && compute_header_checksum(rom) == rom[HEADER_CHECKSUM]
+}
+
+pub fn ram_size_from_code(code: u8) -> Option<usize>{
+    match code{
+        0x00 => Some(0),
+        0x01 => Some(0x800),
+        0x02 => Some(0x2000),
+        0x03 => Some(0x8000),
+        0x04 => Some(0x20000),
+        0x05 => Some(0x10000),
+        _ => None
+    }
+}
+
+fn ascii_string(bytes: &[u8]) -> String{
+    bytes.iter()
+        .take_while(|c| **c != 0)
+        .map(|c| if c.is_ascii_graphic() || *c == b' ' { *c as char } else { '?' })
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
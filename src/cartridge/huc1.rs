@@ -0,0 +1,98 @@
+use crate::infrared::{Infrared, NoInfrared};
+use super::mapper::{store, Mapper};
+
+// HuC1 - MBC1 like banking, 0xA000 - 0xBFFF switches between RAM and an IR transceiver
+pub struct HuC1{
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rom_bank: u8,
+    ram_bank: u8,
+    ir_mode: bool, // Selected by writing 0x0E to 0x0000 - 0x1FFF
+    rom_bitmask: u8,
+    ram_bitmask: u8,
+    infrared: Box<dyn Infrared>
+}
+
+impl HuC1{
+
+    pub fn new(rom: Vec<u8>, ram: Vec<u8>) -> HuC1{
+        let rom_bitmask = ((rom.len() / 0x4000 - 1) & 0x3F) as u8;
+        let ram_bitmask = ((ram.len() / 0x2000).max(1) - 1) as u8 & 0x03;
+
+        HuC1{
+            rom: rom,
+            ram: ram,
+            rom_bank: 1,
+            ram_bank: 0,
+            ir_mode: false,
+            rom_bitmask: rom_bitmask,
+            ram_bitmask: ram_bitmask,
+            infrared: Box::new(NoInfrared)
+        }
+    }
+
+    fn ram_offset(&self, loc: u16) -> usize{
+        ((self.ram_bank & self.ram_bitmask) as usize * 0x2000 + (loc - 0xA000) as usize) % self.ram.len()
+    }
+}
+
+impl Mapper for HuC1{
+
+    fn set_infrared(&mut self, infrared: Box<dyn Infrared>){
+        self.infrared = infrared;
+    }
+
+    fn save_data(&self) -> &[u8]{
+        &self.ram
+    }
+
+    fn save_data_mut(&mut self) -> &mut [u8]{
+        &mut self.ram
+    }
+
+    fn read_rom(&self, loc: u16) -> u8{
+        match loc{
+            0x0000..=0x3FFF => self.rom[loc as usize],
+            _ => {
+                let rom_bank = (self.rom_bank & self.rom_bitmask) as usize;
+                self.rom[rom_bank * 0x4000 + (loc - 0x4000) as usize]
+            }
+        }
+    }
+
+    fn write_rom(&mut self, loc: u16, val: u8){
+        match loc{
+            0x0000..=0x1FFF => self.ir_mode = val == 0x0E,
+            0x2000..=0x3FFF => {
+                self.rom_bank = match val & 0x3F{
+                    0x0 => 1,
+                    bank => bank
+                };
+            }
+            0x4000..=0x5FFF => self.ram_bank = val & 0x03,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, loc: u16) -> u8{
+        if self.ir_mode{
+            return 0xC0 | self.infrared.light_detected() as u8;
+        }
+        if self.ram.is_empty(){
+            return 0xFF;
+        }
+        self.ram[self.ram_offset(loc)]
+    }
+
+    fn write_ram(&mut self, loc: u16, val: u8) -> bool{
+        if self.ir_mode{
+            self.infrared.set_led((val & 0x01) != 0);
+            return false;
+        }
+        if self.ram.is_empty(){
+            return false;
+        }
+        let offset = self.ram_offset(loc);
+        store(&mut self.ram, offset, val)
+    }
+}
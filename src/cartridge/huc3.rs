@@ -0,0 +1,264 @@
+use std::time::{Duration, SystemTime};
+
+use crate::infrared::{Infrared, NoInfrared};
+use super::mapper::{store, Mapper};
+use super::rtc::{encode_footer, decode_footer, seconds_since, unix_time, RtcClock, CYCLES_PER_SECOND};
+
+const MINUTES_PER_DAY: u16 = 1440;
+const CYCLES_PER_MINUTE: u32 = CYCLES_PER_SECOND * 60;
+
+// HuC3 - ROM / RAM banking plus a 4 bit MCU running a clock, a speaker and an IR transceiver
+// The mode register picks what 0xA000 - 0xBFFF talks to:
+// 0x0 - RAM ( read only ), 0xA - RAM, 0xB - RTC command, 0xC - RTC result, 0xD - RTC semaphore, 0xE - IR
+pub struct HuC3{
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rom_bank: u8,
+    ram_bank: u8,
+    mode: u8,
+    rom_bitmask: u8,
+    ram_bitmask: u8,
+    minutes: u16, // Minute of the day
+    days: u16,
+    clock: RtcClock,
+    cycles: u32,          // Emulated clock: cycles into the current minute
+    last_sync: SystemTime, // Host clock: time the clock was last brought up to date
+    rtc_memory: [u8; 256], // Nibbles, 0x00 - 0x06 receive the time on command 0x60
+    rtc_address: u8,
+    rtc_result: u8,
+    infrared: Box<dyn Infrared>
+}
+
+impl HuC3{
+
+    pub fn new(rom: Vec<u8>, ram: Vec<u8>) -> HuC3{
+        let rom_bitmask = ((rom.len() / 0x4000 - 1) & 0x7F) as u8;
+        let ram_bitmask = ((ram.len() / 0x2000).max(1) - 1) as u8 & 0x03;
+
+        HuC3{
+            rom: rom,
+            ram: ram,
+            rom_bank: 1,
+            ram_bank: 0,
+            mode: 0,
+            rom_bitmask: rom_bitmask,
+            ram_bitmask: ram_bitmask,
+            minutes: 0,
+            days: 0,
+            clock: RtcClock::Host,
+            cycles: 0,
+            last_sync: SystemTime::now(),
+            rtc_memory: [0; 256],
+            rtc_address: 0,
+            rtc_result: 0,
+            infrared: Box::new(NoInfrared)
+        }
+    }
+
+    // Catch the clock up with the host clock, whole minutes at a time
+    fn sync(&mut self){
+        if self.clock != RtcClock::Host{
+            return;
+        }
+        let now = SystemTime::now();
+        let elapsed = match now.duration_since(self.last_sync){
+            Ok(elapsed) => elapsed.as_secs() / 60,
+            Err(_) => { self.last_sync = now; return; }
+        };
+        self.last_sync += Duration::from_secs(elapsed * 60);
+        self.advance(elapsed);
+    }
+
+    fn advance(&mut self, elapsed: u64){
+        let total = self.minutes as u64 + elapsed;
+        self.minutes = (total % MINUTES_PER_DAY as u64) as u16;
+        self.days = self.days.wrapping_add((total / MINUTES_PER_DAY as u64) as u16);
+    }
+
+    fn rtc_command(&mut self, val: u8){
+        let arg = val & 0x0F;
+        match val >> 4{
+            0x1 => { // Read nibble, post increment
+                self.rtc_result = self.rtc_memory[self.rtc_address as usize];
+                self.rtc_address = self.rtc_address.wrapping_add(1);
+            }
+            0x3 => { // Write nibble, post increment
+                self.rtc_memory[self.rtc_address as usize] = arg;
+                self.rtc_address = self.rtc_address.wrapping_add(1);
+            }
+            0x4 => self.rtc_address = (self.rtc_address & 0xF0) | arg,
+            0x5 => self.rtc_address = (self.rtc_address & 0x0F) | (arg << 4),
+            0x6 => {
+                match arg{
+                    0x0 => { // Copy the clock into memory, 3 nibbles of minutes then 4 nibbles of days
+                        self.sync();
+                        for i in 0..3{
+                            self.rtc_memory[i] = ((self.minutes >> (i * 4)) & 0x0F) as u8;
+                        }
+                        for i in 0..4{
+                            self.rtc_memory[3 + i] = ((self.days >> (i * 4)) & 0x0F) as u8;
+                        }
+                    }
+                    0x1 => { // Set the clock from memory
+                        let mut minutes = 0;
+                        let mut days = 0;
+                        for i in 0..3{
+                            minutes |= (self.rtc_memory[i] as u16) << (i * 4);
+                        }
+                        for i in 0..4{
+                            days |= (self.rtc_memory[3 + i] as u16) << (i * 4);
+                        }
+                        self.minutes = minutes % MINUTES_PER_DAY;
+                        self.days = days;
+                        self.cycles = 0;
+                        self.last_sync = SystemTime::now();
+                    }
+                    0x2 => self.rtc_result = 0x01, // Status: ready
+                    _ => {} // 0xE plays a tone through the cart speaker, there is no APU to send it to
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ram_offset(&self, loc: u16) -> usize{
+        ((self.ram_bank & self.ram_bitmask) as usize * 0x2000 + (loc - 0xA000) as usize) % self.ram.len()
+    }
+}
+
+impl Mapper for HuC3{
+
+    fn set_rtc_clock(&mut self, clock: RtcClock){
+        self.sync();
+        self.clock = clock;
+        self.cycles = 0;
+        self.last_sync = SystemTime::now();
+    }
+
+    fn step(&mut self, m_cycles: u8){
+        if self.clock != RtcClock::Emulated{
+            return;
+        }
+        self.cycles += m_cycles as u32 * 4;
+        if self.cycles >= CYCLES_PER_MINUTE{
+            self.cycles -= CYCLES_PER_MINUTE;
+            self.advance(1);
+        }
+    }
+
+    fn set_infrared(&mut self, infrared: Box<dyn Infrared>){
+        self.infrared = infrared;
+    }
+
+    fn save_data(&self) -> &[u8]{
+        &self.ram
+    }
+
+    fn save_data_mut(&mut self) -> &mut [u8]{
+        &mut self.ram
+    }
+
+    // Stored in the MBC3 footer layout. The day high word only holds day bit 8, as MBC3 uses bits 6 and 7 of it
+    // for the halt and day carry flags, so the rest of the 16 bit day counter goes in the unused latched seconds
+    fn rtc_footer(&self) -> Option<Vec<u8>>{
+        let current = [0, (self.minutes % 60) as u32, (self.minutes / 60) as u32, self.days as u32 & 0xFF, (self.days >> 8) as u32 & 0x01];
+        let mut latched = current;
+        latched[0] = (self.days >> 9) as u32;
+        let timestamp = match self.clock{
+            RtcClock::Host => unix_time(self.last_sync),
+            RtcClock::Emulated => unix_time(SystemTime::now())
+        };
+        Some(encode_footer(current, latched, timestamp))
+    }
+
+    fn load_rtc_footer(&mut self, footer: &[u8]){
+        if let Some((current, latched, timestamp)) = decode_footer(footer){
+            // A damaged or foreign footer is ignored rather than turned into a nonsense time
+            if current[1] >= 60 || current[2] >= 24{
+                return;
+            }
+            self.minutes = (current[2] * 60 + current[1]) as u16;
+            self.days = (current[3] & 0xFF) as u16 | (((current[4] & 0x01) as u16) << 8) | (((latched[0] & 0x7F) as u16) << 9);
+            match self.clock{
+                RtcClock::Host => {
+                    // Let the next sync catch up from the time of the save
+                    let now = SystemTime::now();
+                    self.last_sync = now.checked_sub(Duration::from_secs(seconds_since(timestamp))).unwrap_or(now);
+                }
+                RtcClock::Emulated => {
+                    self.cycles = 0;
+                    self.advance(seconds_since(timestamp) / 60);
+                }
+            }
+        }
+    }
+
+    fn read_rom(&self, loc: u16) -> u8{
+        match loc{
+            0x0000..=0x3FFF => self.rom[loc as usize],
+            _ => {
+                let rom_bank = (self.rom_bank & self.rom_bitmask) as usize;
+                self.rom[rom_bank * 0x4000 + (loc - 0x4000) as usize]
+            }
+        }
+    }
+
+    fn write_rom(&mut self, loc: u16, val: u8){
+        match loc{
+            0x0000..=0x1FFF => self.mode = val & 0x0F,
+            0x2000..=0x3FFF => {
+                self.rom_bank = match val & 0x7F{
+                    0x0 => 1,
+                    bank => bank
+                };
+            }
+            0x4000..=0x5FFF => self.ram_bank = val & 0x03,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, loc: u16) -> u8{
+        match self.mode{
+            0x0 | 0xA if !self.ram.is_empty() => self.ram[self.ram_offset(loc)],
+            0xC => self.rtc_result,
+            0xD => 0x01, // Commands complete immediately, the MCU is always ready
+            0xE => 0xC0 | self.infrared.light_detected() as u8,
+            _ => 0xFF
+        }
+    }
+
+    fn write_ram(&mut self, loc: u16, val: u8) -> bool{
+        match self.mode{
+            0xA if !self.ram.is_empty() => {
+                let offset = self.ram_offset(loc);
+                return store(&mut self.ram, offset, val);
+            }
+            0xB => self.rtc_command(val),
+            0xE => self.infrared.set_led((val & 0x01) != 0),
+            _ => {}
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn footer_keeps_large_day_counts_out_of_the_mbc3_flags(){
+        let mut huc3 = HuC3::new(vec![0; 0x8000], vec![0; 0x2000]);
+        huc3.set_rtc_clock(RtcClock::Emulated);
+        huc3.minutes = 1000;
+        huc3.days = 0x4321;
+
+        let footer = huc3.rtc_footer().unwrap();
+        let (current, _, _) = decode_footer(&footer).unwrap();
+        assert_eq!(current[4] & 0xC0, 0);
+
+        let mut loaded = HuC3::new(vec![0; 0x8000], vec![0; 0x2000]);
+        loaded.set_rtc_clock(RtcClock::Emulated);
+        loaded.load_rtc_footer(&footer);
+        assert_eq!((loaded.minutes, loaded.days), (1000, 0x4321));
+    }
+}
@@ -0,0 +1,74 @@
+use std::{fs, io::{self, Cursor, Read}, path::Path};
+
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+use super::error::LoadError;
+use super::patch::{apply_patch, find_patch};
+
+const ZIP_MAGIC: [u8; 4] = [0x50, 0x4B, 0x03, 0x04];
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+const ROM_EXTENSIONS: [&str; 2] = ["gb", "gbc"];
+
+// Reads a .gb / .gbc file, or the first ROM inside a .zip / .gz archive
+pub fn load_rom_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, LoadError>{
+    let path = path.as_ref();
+    let data = fs::read(path).map_err(|e| LoadError::from_io(e, path))?;
+    extract_rom(data)
+}
+
+// Applies the given patch, or one found next to the ROM, before the image reaches Cartridge
+pub fn load_patched_rom(rom_path: &str, patch_path: Option<&Path>) -> Result<Vec<u8>, LoadError>{
+    let rom = load_rom_file(rom_path)?;
+
+    let patch_path = match patch_path{
+        Some(path) => path.to_path_buf(),
+        None => match find_patch(rom_path){
+            Some(path) => path,
+            None => return Ok(rom)
+        }
+    };
+
+    let bad_patch = |e: io::Error| LoadError::BadPatch{ path: patch_path.clone(), reason: e.to_string() };
+    let patch = fs::read(&patch_path).map_err(bad_patch)?;
+    let patched = apply_patch(&rom, &patch).map_err(bad_patch)?;
+    println!("Applied patch {}", patch_path.display());
+    Ok(patched)
+}
+
+// Archives are recognised from their magic bytes so misnamed files still load
+pub fn extract_rom(data: Vec<u8>) -> Result<Vec<u8>, LoadError>{
+    if data.starts_with(&ZIP_MAGIC){
+        extract_zip(data)
+    }
+    else if data.starts_with(&GZIP_MAGIC){
+        let mut rom = Vec::new();
+        GzDecoder::new(&data[..]).read_to_end(&mut rom)?;
+        Ok(rom)
+    }
+    else{
+        Ok(data)
+    }
+}
+
+fn extract_zip(data: Vec<u8>) -> Result<Vec<u8>, LoadError>{
+    let mut archive = ZipArchive::new(Cursor::new(data)).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    for i in 0..archive.len(){
+        let mut file = archive.by_index(i).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let is_rom = Path::new(file.name()).extension()
+            .and_then(|ext| ext.to_str())
+            .map_or(false, |ext| ROM_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+        if !is_rom{
+            continue;
+        }
+
+        println!("Loading {} from archive", file.name());
+        let mut rom = Vec::new();
+        file.read_to_end(&mut rom)?;
+        return Ok(rom);
+    }
+
+    Err(LoadError::Io(io::Error::new(io::ErrorKind::NotFound, "No .gb or .gbc file in archive")))
+}
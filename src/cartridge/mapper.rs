@@ -0,0 +1,69 @@
+use std::sync::Mutex;
+
+use super::camera::CameraImageProvider;
+use super::mbc5::RumbleCallback;
+use super::rtc::RtcClock;
+use crate::infrared::Infrared;
+
+// Memory bank controller of a cartridge. ROM is 0x0000 - 0x7FFF, writes there go to the mapper registers,
+// RAM is 0xA000 - 0xBFFF. The optional peripherals default to doing nothing for mappers without them.
+pub trait Mapper{
+    fn read_rom(&self, loc: u16) -> u8;
+    fn write_rom(&mut self, loc: u16, val: u8);
+    fn read_ram(&self, loc: u16) -> u8;
+    // Returns true if battery backed data changed, so the save only gets rewritten when needed
+    fn write_ram(&mut self, loc: u16, val: u8) -> bool;
+
+    // Battery backed memory, as stored in the .sav file
+    fn save_data(&self) -> &[u8]{
+        &[]
+    }
+
+    fn save_data_mut(&mut self) -> &mut [u8]{
+        &mut []
+    }
+
+    // Clock state appended to the .sav file, see rtc::encode_footer
+    fn rtc_footer(&self) -> Option<Vec<u8>>{
+        None
+    }
+
+    fn load_rtc_footer(&mut self, _footer: &[u8]){}
+
+    // Ticks the RTC and other timed hardware
+    fn step(&mut self, _m_cycles: u8){}
+
+    fn set_rtc_clock(&mut self, _clock: RtcClock){}
+
+    fn set_rumble_callback(&mut self, _callback: RumbleCallback){}
+
+    // Accelerometer, x and y are in g
+    fn set_tilt(&mut self, _x: f32, _y: f32){}
+
+    fn set_infrared(&mut self, _infrared: Box<dyn Infrared>){}
+
+    fn set_camera_image_provider(&mut self, _provider: CameraImageProvider){}
+}
+
+// Stores a byte of external RAM, true if that changed it
+pub fn store(ram: &mut [u8], offset: usize, val: u8) -> bool{
+    let changed = ram[offset] != val;
+    ram[offset] = val;
+    changed
+}
+
+// Builds a mapper from the padded ROM and the external RAM sized from the header
+pub type MapperConstructor = fn(rom: Vec<u8>, ram: Vec<u8>) -> Box<dyn Mapper>;
+
+static REGISTERED: Mutex<Vec<(u8, MapperConstructor)>> = Mutex::new(Vec::new());
+
+// Makes cartridges with the given type byte use a custom mapper, taking precedence over the built in ones
+pub fn register_mapper(cartridge_type: u8, constructor: MapperConstructor){
+    let mut registered = REGISTERED.lock().unwrap();
+    registered.retain(|(t, _)| *t != cartridge_type);
+    registered.push((cartridge_type, constructor));
+}
+
+pub fn registered_mapper(cartridge_type: u8) -> Option<MapperConstructor>{
+    REGISTERED.lock().unwrap().iter().find(|(t, _)| *t == cartridge_type).map(|(_, c)| *c)
+}
@@ -0,0 +1,126 @@
+use super::mapper::{store, Mapper};
+
+// MBC1 - up to 2 MiB ROM / 32 KiB RAM, MBC1M multicarts rewire the upper bank register
+pub struct Mbc1{
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    bank1_reg: u8, // 0x2000 - 0x3FFF : Lower 5 bits of rom bank
+    bank2_reg: u8, // 0x4000 - 0x5FFF : Upper 2 bits of rom bank or ram bank
+    ram_enabled: bool,
+    mbc_mode: u8, // 0 - Simple banking, 1 - Advanced banking ( bank2 also applies to 0x0000-0x3FFF and RAM )
+    rom_bitmask: u16,
+    multicart: bool
+}
+
+impl Mbc1{
+
+    pub fn new(rom: Vec<u8>, ram: Vec<u8>) -> Mbc1{
+        let rom_bitmask = (rom.len() / 0x4000 - 1) as u16;
+        let multicart = Mbc1::is_multicart(&rom);
+        if multicart{
+            println!("Detected MBC1M multicart wiring");
+        }
+
+        Mbc1{
+            rom: rom,
+            ram: ram,
+            bank1_reg: 1,
+            bank2_reg: 0,
+            ram_enabled: false,
+            mbc_mode: 0,
+            rom_bitmask: rom_bitmask,
+            multicart: multicart
+        }
+    }
+
+    // MBC1M carts are 1 MiB and every 256 KiB game starts with its own copy of the header logo
+    fn is_multicart(rom: &[u8]) -> bool{
+        rom.len() == 0x100000 && rom[0x0104..0x0134] == rom[0x40104..0x40134]
+    }
+
+    fn upper_bank_bits(&self) -> u16{
+        if self.multicart{
+            (self.bank2_reg as u16) << 4
+        }
+        else{
+            (self.bank2_reg as u16) << 5
+        }
+    }
+
+    fn rom_bank_0(&self) -> u16{
+        if self.mbc_mode == 0{
+            0
+        }
+        else{
+            self.upper_bank_bits() & self.rom_bitmask
+        }
+    }
+
+    fn rom_bank_n(&self) -> u16{
+        let lower_bits = if self.multicart {self.bank1_reg & 0x0F} else {self.bank1_reg};
+        (self.upper_bank_bits() | lower_bits as u16) & self.rom_bitmask
+    }
+
+    fn ram_offset(&self, loc: u16) -> usize{
+        let ram_bank = if self.mbc_mode == 1 {self.bank2_reg as usize} else {0};
+        (ram_bank * 0x2000 + (loc - 0xA000) as usize) % self.ram.len()
+    }
+}
+
+impl Mapper for Mbc1{
+
+    fn save_data(&self) -> &[u8]{
+        &self.ram
+    }
+
+    fn save_data_mut(&mut self) -> &mut [u8]{
+        &mut self.ram
+    }
+
+    fn read_rom(&self, loc: u16) -> u8{
+        match loc{
+            0x0000..=0x3FFF => {
+                self.rom[self.rom_bank_0() as usize * 0x4000 + loc as usize]
+            }
+            _ => {
+                self.rom[self.rom_bank_n() as usize * 0x4000 + (loc - 0x4000) as usize]
+            }
+        }
+    }
+
+    fn write_rom(&mut self, loc: u16, val: u8){
+        match loc{
+            0x0000..=0x1FFF => {
+                self.ram_enabled = (val & 0x0F) == 0x0A;
+            }
+            0x2000..=0x3FFF => {
+                // Bank 0 can not be selected through the lower 5 bits, the zero check ignores the upper bits
+                self.bank1_reg = match val & 0x1F{
+                    0x0 => 1,
+                    bank => bank
+                };
+            }
+            0x4000..=0x5FFF => {
+                self.bank2_reg = val & 0x03;
+            }
+            _ => {
+                self.mbc_mode = val & 0x01;
+            }
+        }
+    }
+
+    fn read_ram(&self, loc: u16) -> u8{
+        if !self.ram_enabled || self.ram.is_empty(){
+            return 0xFF;
+        }
+        self.ram[self.ram_offset(loc)]
+    }
+
+    fn write_ram(&mut self, loc: u16, val: u8) -> bool{
+        if !self.ram_enabled || self.ram.is_empty(){
+            return false;
+        }
+        let offset = self.ram_offset(loc);
+        store(&mut self.ram, offset, val)
+    }
+}
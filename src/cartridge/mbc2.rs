@@ -0,0 +1,80 @@
+use super::mapper::{store, Mapper};
+
+const MBC2_RAM_SIZE: usize = 512;
+
+// MBC2 - up to 256 KiB ROM and 512 x 4 bit RAM built into the mapper
+pub struct Mbc2{
+    rom: Vec<u8>,
+    ram: Vec<u8>, // Only the lower nibble of each byte is stored
+    rom_bank: u8,
+    ram_enabled: bool,
+    rom_bitmask: u8
+}
+
+impl Mbc2{
+
+    pub fn new(rom: Vec<u8>) -> Mbc2{
+        let rom_bitmask = ((rom.len() / 0x4000 - 1) & 0x0F) as u8;
+
+        Mbc2{
+            rom: rom,
+            ram: vec![0; MBC2_RAM_SIZE],
+            rom_bank: 1,
+            ram_enabled: false,
+            rom_bitmask: rom_bitmask
+        }
+    }
+}
+
+impl Mapper for Mbc2{
+
+    fn save_data(&self) -> &[u8]{
+        &self.ram
+    }
+
+    fn save_data_mut(&mut self) -> &mut [u8]{
+        &mut self.ram
+    }
+
+    fn read_rom(&self, loc: u16) -> u8{
+        match loc{
+            0x0000..=0x3FFF => self.rom[loc as usize],
+            _ => {
+                let rom_bank = (self.rom_bank & self.rom_bitmask) as usize;
+                self.rom[rom_bank * 0x4000 + (loc - 0x4000) as usize]
+            }
+        }
+    }
+
+    fn write_rom(&mut self, loc: u16, val: u8){
+        // Both registers live in 0x0000-0x3FFF, address bit 8 picks which one is written
+        if loc > 0x3FFF{
+            return;
+        }
+
+        if (loc & 0x0100) == 0{
+            self.ram_enabled = (val & 0x0F) == 0x0A;
+        }
+        else{
+            self.rom_bank = match val & 0x0F{
+                0x0 => 1,
+                bank => bank
+            };
+        }
+    }
+
+    fn read_ram(&self, loc: u16) -> u8{
+        if !self.ram_enabled{
+            return 0xFF;
+        }
+        // 512 half bytes mirrored across the whole range, the upper nibble is not wired
+        0xF0 | self.ram[(loc as usize) & (MBC2_RAM_SIZE - 1)]
+    }
+
+    fn write_ram(&mut self, loc: u16, val: u8) -> bool{
+        if !self.ram_enabled{
+            return false;
+        }
+        store(&mut self.ram, (loc as usize) & (MBC2_RAM_SIZE - 1), val & 0x0F)
+    }
+}
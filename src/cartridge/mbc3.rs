@@ -0,0 +1,143 @@
+use super::rtc::{Rtc, RtcClock};
+use super::mapper::{store, Mapper};
+
+// MBC3 - up to 2 MiB ROM / 32 KiB RAM and an optional real time clock
+// MBC30 ( Pocket Monsters Crystal JP ) widens the ROM bank to 8 bits and the RAM bank to 3 bits
+pub struct Mbc3{
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rom_bank: u8,
+    rom_bank_mask: u8, // 7 bits on MBC3, 8 bits on MBC30
+    ram_bank: u8, // 0x00 - 0x07 select a RAM bank, 0x08 - 0x0C map an RTC register
+    ram_enabled: bool, // Also gates the RTC registers
+    rom_bitmask: u8,
+    ram_bitmask: u8,
+    rtc: Option<Rtc>
+}
+
+impl Mbc3{
+
+    pub fn new(rom: Vec<u8>, ram: Vec<u8>, has_rtc: bool) -> Mbc3{
+        let mbc30 = rom.len() > 0x200000 || ram.len() > 0x8000;
+        if mbc30{
+            println!("Using MBC30 address space");
+        }
+
+        let rom_bank_mask = if mbc30 {0xFF} else {0x7F};
+        let ram_bank_mask = if mbc30 {0x07} else {0x03};
+        let rom_bitmask = ((rom.len() / 0x4000 - 1) & rom_bank_mask) as u8;
+        let ram_bitmask = ((ram.len() / 0x2000).max(1) - 1) as u8 & ram_bank_mask;
+
+        Mbc3{
+            rom: rom,
+            ram: ram,
+            rom_bank: 1,
+            rom_bank_mask: rom_bank_mask as u8,
+            ram_bank: 0,
+            ram_enabled: false,
+            rom_bitmask: rom_bitmask,
+            ram_bitmask: ram_bitmask,
+            rtc: if has_rtc {Some(Rtc::new(RtcClock::Host))} else {None}
+        }
+    }
+
+    fn ram_offset(&self, loc: u16) -> usize{
+        ((self.ram_bank & self.ram_bitmask) as usize * 0x2000 + (loc - 0xA000) as usize) % self.ram.len()
+    }
+}
+
+impl Mapper for Mbc3{
+
+    fn set_rtc_clock(&mut self, clock: RtcClock){
+        if let Some(rtc) = &mut self.rtc{
+            rtc.set_clock(clock);
+        }
+    }
+
+    fn step(&mut self, m_cycles: u8){
+        if let Some(rtc) = &mut self.rtc{
+            rtc.step(m_cycles);
+        }
+    }
+
+    fn save_data(&self) -> &[u8]{
+        &self.ram
+    }
+
+    fn save_data_mut(&mut self) -> &mut [u8]{
+        &mut self.ram
+    }
+
+    fn rtc_footer(&self) -> Option<Vec<u8>>{
+        self.rtc.as_ref().map(|rtc| rtc.footer())
+    }
+
+    fn load_rtc_footer(&mut self, footer: &[u8]){
+        if let Some(rtc) = &mut self.rtc{
+            rtc.load_footer(footer);
+        }
+    }
+
+    fn read_rom(&self, loc: u16) -> u8{
+        match loc{
+            0x0000..=0x3FFF => self.rom[loc as usize],
+            _ => {
+                let rom_bank = (self.rom_bank & self.rom_bitmask) as usize;
+                self.rom[rom_bank * 0x4000 + (loc - 0x4000) as usize]
+            }
+        }
+    }
+
+    fn write_rom(&mut self, loc: u16, val: u8){
+        match loc{
+            0x0000..=0x1FFF => {
+                self.ram_enabled = (val & 0x0F) == 0x0A;
+            }
+            0x2000..=0x3FFF => {
+                self.rom_bank = match val & self.rom_bank_mask{
+                    0x0 => 1,
+                    bank => bank
+                };
+            }
+            0x4000..=0x5FFF => {
+                self.ram_bank = val & 0x0F;
+            }
+            _ => {
+                if let Some(rtc) = &mut self.rtc{
+                    rtc.write_latch(val);
+                }
+            }
+        }
+    }
+
+    fn read_ram(&self, loc: u16) -> u8{
+        if !self.ram_enabled{
+            return 0xFF;
+        }
+        match (self.ram_bank, &self.rtc){
+            (0x08..=0x0C, Some(rtc)) => rtc.read(self.ram_bank),
+            (0x00..=0x07, _) if !self.ram.is_empty() => self.ram[self.ram_offset(loc)],
+            _ => 0xFF
+        }
+    }
+
+    fn write_ram(&mut self, loc: u16, val: u8) -> bool{
+        if !self.ram_enabled{
+            return false;
+        }
+        match self.ram_bank{
+            0x08..=0x0C => {
+                // The clock is saved on exit, setting it does not need an immediate flush
+                if let Some(rtc) = &mut self.rtc{
+                    rtc.write(self.ram_bank, val);
+                }
+                false
+            }
+            0x00..=0x07 if !self.ram.is_empty() => {
+                let offset = self.ram_offset(loc);
+                store(&mut self.ram, offset, val)
+            }
+            _ => false
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use super::mapper::{store, Mapper};
+
+pub type RumbleCallback = Box<dyn FnMut(bool)>;
+
+// MBC5 - up to 8 MiB ROM ( 9 bit bank number ) and 128 KiB RAM
+// Rumble carts wire bit 3 of the RAM bank register to the motor instead
+pub struct Mbc5{
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rom_bank: u16, // Unlike other MBCs bank 0 can be mapped to 0x4000 - 0x7FFF
+    ram_bank: u8,
+    ram_enabled: bool,
+    rom_bitmask: u16,
+    ram_bitmask: u8,
+    has_rumble: bool,
+    motor_on: bool,
+    rumble_callback: Option<RumbleCallback>
+}
+
+impl Mbc5{
+
+    pub fn new(rom: Vec<u8>, ram: Vec<u8>, has_rumble: bool) -> Mbc5{
+        let rom_bitmask = ((rom.len() / 0x4000 - 1) & 0x1FF) as u16;
+        let ram_bitmask = ((ram.len() / 0x2000).max(1) - 1) as u8 & 0x0F;
+
+        Mbc5{
+            rom: rom,
+            ram: ram,
+            rom_bank: 1,
+            ram_bank: 0,
+            ram_enabled: false,
+            rom_bitmask: rom_bitmask,
+            ram_bitmask: ram_bitmask,
+            has_rumble: has_rumble,
+            motor_on: false,
+            rumble_callback: None
+        }
+    }
+
+    fn set_motor(&mut self, on: bool){
+        if self.motor_on == on{
+            return;
+        }
+        self.motor_on = on;
+        if let Some(callback) = &mut self.rumble_callback{
+            callback(on);
+        }
+    }
+
+    fn ram_offset(&self, loc: u16) -> usize{
+        ((self.ram_bank & self.ram_bitmask) as usize * 0x2000 + (loc - 0xA000) as usize) % self.ram.len()
+    }
+}
+
+impl Mapper for Mbc5{
+
+    // Called with the new motor state every time the game switches it on or off
+    fn set_rumble_callback(&mut self, callback: RumbleCallback){
+        self.rumble_callback = Some(callback);
+    }
+
+    fn save_data(&self) -> &[u8]{
+        &self.ram
+    }
+
+    fn save_data_mut(&mut self) -> &mut [u8]{
+        &mut self.ram
+    }
+
+    fn read_rom(&self, loc: u16) -> u8{
+        match loc{
+            0x0000..=0x3FFF => self.rom[loc as usize],
+            _ => {
+                let rom_bank = (self.rom_bank & self.rom_bitmask) as usize;
+                self.rom[rom_bank * 0x4000 + (loc - 0x4000) as usize]
+            }
+        }
+    }
+
+    fn write_rom(&mut self, loc: u16, val: u8){
+        match loc{
+            0x0000..=0x1FFF => {
+                // MBC5 compares all 8 bits
+                self.ram_enabled = val == 0x0A;
+            }
+            0x2000..=0x2FFF => {
+                self.rom_bank = (self.rom_bank & 0x100) | val as u16;
+            }
+            0x3000..=0x3FFF => {
+                self.rom_bank = (self.rom_bank & 0xFF) | (((val & 0x01) as u16) << 8);
+            }
+            0x4000..=0x5FFF => {
+                if self.has_rumble{
+                    self.ram_bank = val & 0x07;
+                    self.set_motor((val & 0x08) != 0);
+                }
+                else{
+                    self.ram_bank = val & 0x0F;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, loc: u16) -> u8{
+        if !self.ram_enabled || self.ram.is_empty(){
+            return 0xFF;
+        }
+        self.ram[self.ram_offset(loc)]
+    }
+
+    fn write_ram(&mut self, loc: u16, val: u8) -> bool{
+        if !self.ram_enabled || self.ram.is_empty(){
+            return false;
+        }
+        let offset = self.ram_offset(loc);
+        store(&mut self.ram, offset, val)
+    }
+}
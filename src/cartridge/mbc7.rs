@@ -0,0 +1,116 @@
+use super::eeprom::Eeprom;
+use super::mapper::Mapper;
+
+const ACCEL_CENTER: f32 = 0x81D0 as f32; // Reading when the console is held flat
+const ACCEL_ONE_G: f32 = 0x70 as f32;
+const ACCEL_ERASED: u16 = 0x8000;
+
+// MBC7 - ROM banking, a 2 axis accelerometer and a 93LC56 EEPROM in place of RAM
+pub struct Mbc7{
+    rom: Vec<u8>,
+    rom_bank: u8,
+    rom_bitmask: u8,
+    ram_enable_1: bool, // 0x0000 - 0x1FFF : 0x0A
+    ram_enable_2: bool, // 0x4000 - 0x5FFF : 0x40
+    tilt_x: f32, // In g, positive tilts right
+    tilt_y: f32, // In g, positive tilts towards the player
+    accel_x: u16,
+    accel_y: u16,
+    eeprom: Eeprom
+}
+
+impl Mbc7{
+
+    pub fn new(rom: Vec<u8>) -> Mbc7{
+        let rom_bitmask = ((rom.len() / 0x4000 - 1) & 0x7F) as u8;
+
+        Mbc7{
+            rom: rom,
+            rom_bank: 1,
+            rom_bitmask: rom_bitmask,
+            ram_enable_1: false,
+            ram_enable_2: false,
+            tilt_x: 0.0,
+            tilt_y: 0.0,
+            accel_x: ACCEL_ERASED,
+            accel_y: ACCEL_ERASED,
+            eeprom: Eeprom::new()
+        }
+    }
+}
+
+impl Mapper for Mbc7{
+
+    // Values are in g and clamped to the sensor range, the game only sees them on its next latch
+    fn set_tilt(&mut self, x: f32, y: f32){
+        self.tilt_x = x.max(-2.0).min(2.0);
+        self.tilt_y = y.max(-2.0).min(2.0);
+    }
+
+    fn save_data(&self) -> &[u8]{
+        self.eeprom.data()
+    }
+
+    fn save_data_mut(&mut self) -> &mut [u8]{
+        self.eeprom.data_mut()
+    }
+
+    fn read_rom(&self, loc: u16) -> u8{
+        match loc{
+            0x0000..=0x3FFF => self.rom[loc as usize],
+            _ => {
+                let rom_bank = (self.rom_bank & self.rom_bitmask) as usize;
+                self.rom[rom_bank * 0x4000 + (loc - 0x4000) as usize]
+            }
+        }
+    }
+
+    fn write_rom(&mut self, loc: u16, val: u8){
+        match loc{
+            0x0000..=0x1FFF => self.ram_enable_1 = val == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = val,
+            0x4000..=0x5FFF => self.ram_enable_2 = val == 0x40,
+            _ => {}
+        }
+    }
+
+    // Registers are selected by address bits 4-7 and mirrored across 0xA000 - 0xAFFF
+    fn read_ram(&self, loc: u16) -> u8{
+        if !self.ram_enable_1 || !self.ram_enable_2 || loc > 0xAFFF{
+            return 0xFF;
+        }
+        match (loc >> 4) & 0x0F{
+            0x2 => self.accel_x as u8,
+            0x3 => (self.accel_x >> 8) as u8,
+            0x4 => self.accel_y as u8,
+            0x5 => (self.accel_y >> 8) as u8,
+            0x6 => 0x00, // No Z axis
+            0x8 => self.eeprom.read(),
+            _ => 0xFF
+        }
+    }
+
+    fn write_ram(&mut self, loc: u16, val: u8) -> bool{
+        if !self.ram_enable_1 || !self.ram_enable_2 || loc > 0xAFFF{
+            return false;
+        }
+        match (loc >> 4) & 0x0F{
+            0x0 => {
+                if val == 0x55{
+                    self.accel_x = ACCEL_ERASED;
+                    self.accel_y = ACCEL_ERASED;
+                }
+            }
+            0x1 => {
+                // Latching only works after the previous sample was erased
+                if val == 0xAA && self.accel_x == ACCEL_ERASED && self.accel_y == ACCEL_ERASED{
+                    self.accel_x = (ACCEL_CENTER + ACCEL_ONE_G * self.tilt_x) as u16;
+                    self.accel_y = (ACCEL_CENTER + ACCEL_ONE_G * self.tilt_y) as u16;
+                }
+            }
+            0x8 => return self.eeprom.write(val),
+            _ => {}
+        }
+        false
+    }
+}
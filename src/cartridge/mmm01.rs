@@ -0,0 +1,163 @@
+use super::mapper::{store, Mapper};
+
+// MMM01 - multicart mapper. Boots into a menu stored in the last 32 KiB of ROM. The menu picks a game by
+// setting the outer bank bits and masks, then sets the map enable bit which locks those in and hands the
+// cart over to the game, which then sees an ordinary MBC1.
+pub struct Mmm01{
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    mapped: bool,        // Set once the menu locks in a game
+    ram_enabled: bool,
+    rom_bank_low: u8,    // 5 bits, 0x2000 - 0x3FFF
+    rom_bank_mid: u8,    // 2 bits, 0x2000 - 0x3FFF bits 5-6 ( menu only )
+    rom_bank_high: u8,   // 2 bits, 0x4000 - 0x5FFF bits 4-5 ( menu only )
+    rom_bank_mask: u8,   // Low bank bits fixed by the menu, 0x6000 - 0x7FFF bits 2-5 ( menu only )
+    ram_bank_low: u8,    // 2 bits, 0x4000 - 0x5FFF
+    ram_bank_high: u8,   // 2 bits, 0x4000 - 0x5FFF bits 2-3 ( menu only )
+    ram_bank_mask: u8,   // RAM bank bits fixed by the menu, 0x0000 - 0x1FFF bits 4-5 ( menu only )
+    mbc_mode: u8,
+    mode_locked: bool,   // 0x4000 - 0x5FFF bit 6 ( menu only )
+    multiplex: bool,     // 0x6000 - 0x7FFF bit 6 ( menu only )
+    rom_bitmask: u16
+}
+
+impl Mmm01{
+
+    pub fn new(rom: Vec<u8>, ram: Vec<u8>) -> Mmm01{
+        let rom_bitmask = (rom.len() / 0x4000 - 1) as u16;
+
+        Mmm01{
+            rom: rom,
+            ram: ram,
+            mapped: false,
+            ram_enabled: false,
+            rom_bank_low: 0,
+            rom_bank_mid: 0,
+            rom_bank_high: 0,
+            rom_bank_mask: 0,
+            ram_bank_low: 0,
+            ram_bank_high: 0,
+            ram_bank_mask: 0,
+            mbc_mode: 0,
+            mode_locked: false,
+            multiplex: false,
+            rom_bitmask: rom_bitmask
+        }
+    }
+
+    // With multiplexing the game's MBC1 mode 1 upper bits drive the ROM mid bits instead of the RAM bank
+    fn effective_banks(&self) -> (u8, u8){
+        if self.multiplex && self.mbc_mode == 1{
+            (self.ram_bank_low, self.rom_bank_mid)
+        }
+        else{
+            (self.rom_bank_mid, self.ram_bank_low)
+        }
+    }
+
+    fn rom_bank(&self, upper_half: bool) -> u16{
+        if !self.mapped{
+            // Unmapped, bank bits 1-8 are forced high which maps the last 32 KiB ( the menu )
+            let bank = if upper_half {0x1FF} else {0x1FE};
+            return bank & self.rom_bitmask;
+        }
+
+        let (rom_mid, _) = self.effective_banks();
+        let mut low = self.rom_bank_low;
+        if upper_half{
+            // Like MBC1, bank 0 is replaced by bank 1 within the bits the game controls
+            if (low & !self.fixed_low_bits()) == 0{
+                low |= 0x01;
+            }
+        }
+        else{
+            low &= self.fixed_low_bits();
+        }
+
+        (((self.rom_bank_high as u16) << 7) | ((rom_mid as u16) << 5) | low as u16) & self.rom_bitmask
+    }
+
+    // Mask bits 2-5 protect ROM bank bits 1-4
+    fn fixed_low_bits(&self) -> u8{
+        (self.rom_bank_mask >> 1) & 0x1E
+    }
+
+    fn ram_offset(&self, loc: u16) -> usize{
+        // Like MBC1 the game's RAM bank bits only apply in mode 1, bits fixed by the menu always apply
+        let (_, ram_low) = self.effective_banks();
+        let ram_bank = if self.mbc_mode == 1 || self.multiplex {ram_low} else {ram_low & self.ram_bank_mask};
+        let bank = ((self.ram_bank_high << 2) | ram_bank) as usize;
+        (bank * 0x2000 + (loc - 0xA000) as usize) % self.ram.len()
+    }
+}
+
+impl Mapper for Mmm01{
+
+    fn save_data(&self) -> &[u8]{
+        &self.ram
+    }
+
+    fn save_data_mut(&mut self) -> &mut [u8]{
+        &mut self.ram
+    }
+
+    fn read_rom(&self, loc: u16) -> u8{
+        match loc{
+            0x0000..=0x3FFF => self.rom[self.rom_bank(false) as usize * 0x4000 + loc as usize],
+            _ => self.rom[self.rom_bank(true) as usize * 0x4000 + (loc - 0x4000) as usize]
+        }
+    }
+
+    fn write_rom(&mut self, loc: u16, val: u8){
+        match loc{
+            0x0000..=0x1FFF => {
+                self.ram_enabled = (val & 0x0F) == 0x0A;
+                if !self.mapped{
+                    self.ram_bank_mask = (val >> 4) & 0x03;
+                    self.mapped = (val & 0x40) != 0;
+                }
+            }
+            0x2000..=0x3FFF => {
+                // After mapping the game can only change the low bits the menu left unmasked
+                let writable = if self.mapped {!self.fixed_low_bits() & 0x1F} else {0x1F};
+                self.rom_bank_low = (self.rom_bank_low & !writable) | (val & writable);
+                if !self.mapped{
+                    self.rom_bank_mid = (val >> 5) & 0x03;
+                }
+            }
+            0x4000..=0x5FFF => {
+                let writable = if self.mapped {!self.ram_bank_mask & 0x03} else {0x03};
+                self.ram_bank_low = (self.ram_bank_low & !writable) | (val & writable);
+                if !self.mapped{
+                    self.ram_bank_high = (val >> 2) & 0x03;
+                    self.rom_bank_high = (val >> 4) & 0x03;
+                    self.mode_locked = (val & 0x40) != 0;
+                }
+            }
+            _ => {
+                if !self.mode_locked{
+                    self.mbc_mode = val & 0x01;
+                }
+                if !self.mapped{
+                    self.rom_bank_mask = val & 0x3C;
+                    self.multiplex = (val & 0x40) != 0;
+                }
+            }
+        }
+    }
+
+    fn read_ram(&self, loc: u16) -> u8{
+        if !self.ram_enabled || self.ram.is_empty(){
+            return 0xFF;
+        }
+        self.ram[self.ram_offset(loc)]
+    }
+
+    fn write_ram(&mut self, loc: u16, val: u8) -> bool{
+        if !self.ram_enabled || self.ram.is_empty(){
+            return false;
+        }
+        let offset = self.ram_offset(loc);
+        store(&mut self.ram, offset, val)
+    }
+}
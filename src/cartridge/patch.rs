@@ -0,0 +1,466 @@
+use std::{io, path::{Path, PathBuf}};
+
+use crate::util::crc32;
+
+const PATCH_EXTENSIONS: [&str; 3] = ["ips", "ups", "bps"];
+
+// Largest ROM a cartridge can hold ( MBC5, 8 MiB ), anything bigger is a broken patch
+const MAX_TARGET_SIZE: usize = 0x800000;
+
+fn invalid(msg: &str) -> io::Error{
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+// <rom>.ips / .ups / .bps next to the ROM, the first one found is used
+pub fn find_patch(rom_path: &str) -> Option<PathBuf>{
+    PATCH_EXTENSIONS.iter()
+        .map(|ext| Path::new(rom_path).with_extension(ext))
+        .find(|path| path.is_file())
+}
+
+// Picks the format from the patch magic
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>>{
+    if patch.starts_with(b"PATCH"){
+        apply_ips(rom, patch)
+    }
+    else if patch.starts_with(b"UPS1"){
+        apply_ups(rom, patch)
+    }
+    else if patch.starts_with(b"BPS1"){
+        apply_bps(rom, patch)
+    }
+    else{
+        Err(invalid("Unknown patch format"))
+    }
+}
+
+struct PatchReader<'a>{
+    data: &'a [u8],
+    pos: usize
+}
+
+impl<'a> PatchReader<'a>{
+
+    fn byte(&mut self) -> io::Result<u8>{
+        let val = *self.data.get(self.pos).ok_or_else(|| invalid("Patch ends unexpectedly"))?;
+        self.pos += 1;
+        Ok(val)
+    }
+
+    fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]>{
+        let end = self.pos.checked_add(len).ok_or_else(|| invalid("Patch ends unexpectedly"))?;
+        let data = self.data.get(self.pos..end).ok_or_else(|| invalid("Patch ends unexpectedly"))?;
+        self.pos += len;
+        Ok(data)
+    }
+
+    fn be(&mut self, len: usize) -> io::Result<usize>{
+        Ok(self.bytes(len)?.iter().fold(0, |acc, b| (acc << 8) | *b as usize))
+    }
+
+    // UPS / BPS variable length number, 7 bits per byte with the high bit marking the last byte
+    fn varint(&mut self) -> io::Result<usize>{
+        let mut data: usize = 0;
+        let mut shift: usize = 1;
+        loop{
+            let x = self.byte()?;
+            let too_large = || invalid("Patch number too large");
+            data = ((x & 0x7F) as usize).checked_mul(shift).and_then(|val| data.checked_add(val)).ok_or_else(too_large)?;
+            if (x & 0x80) != 0{
+                return Ok(data);
+            }
+            shift = shift.checked_mul(0x80).ok_or_else(too_large)?;
+            data = data.checked_add(shift).ok_or_else(too_large)?;
+        }
+    }
+}
+
+fn read_u32_le(data: &[u8]) -> u32{
+    u32::from_le_bytes([data[0], data[1], data[2], data[3]])
+}
+
+// Source, target and patch CRC-32s stored in the last 12 bytes of UPS and BPS patches
+fn verify_footer(source: &[u8], patch: &[u8]) -> io::Result<(u32, usize)>{
+    if patch.len() < 16{
+        return Err(invalid("Patch too short"));
+    }
+    let footer = patch.len() - 12;
+    if crc32(&patch[..patch.len() - 4]) != read_u32_le(&patch[footer + 8..]){
+        return Err(invalid("Patch checksum mismatch, the patch file is damaged"));
+    }
+    if crc32(source) != read_u32_le(&patch[footer..]){
+        return Err(invalid("ROM checksum mismatch, the patch is for a different ROM"));
+    }
+    Ok((read_u32_le(&patch[footer + 4..]), footer))
+}
+
+fn check_target_size(target_size: usize) -> io::Result<()>{
+    if target_size > MAX_TARGET_SIZE{
+        return Err(invalid("Patched ROM would be too large"));
+    }
+    Ok(())
+}
+
+fn verify_target(target: &[u8], expected: u32) -> io::Result<()>{
+    if crc32(target) != expected{
+        return Err(invalid("Patched ROM checksum mismatch"));
+    }
+    Ok(())
+}
+
+// IPS - records of 24 bit offset, 16 bit length and data, a zero length marks a run of one byte
+fn apply_ips(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>>{
+    let mut target = rom.to_vec();
+    let mut reader = PatchReader{ data: patch, pos: 5 };
+
+    loop{
+        if reader.data.get(reader.pos..reader.pos + 3) == Some(b"EOF"){
+            reader.pos += 3;
+            break;
+        }
+
+        let offset = reader.be(3)?;
+        let len = reader.be(2)?;
+        let (len, run) = if len == 0 {(reader.be(2)?, Some(reader.byte()?))} else {(len, None)};
+
+        if target.len() < offset + len{
+            target.resize(offset + len, 0);
+        }
+        match run{
+            Some(val) => target[offset..offset + len].iter_mut().for_each(|b| *b = val),
+            None => target[offset..offset + len].copy_from_slice(reader.bytes(len)?)
+        }
+    }
+
+    // Optional truncation extension
+    if let Ok(len) = reader.be(3){
+        target.truncate(len);
+    }
+
+    Ok(target)
+}
+
+// UPS - XOR runs at relative offsets, each run ends with a zero byte
+fn apply_ups(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>>{
+    let (target_crc, footer) = verify_footer(rom, patch)?;
+    let mut reader = PatchReader{ data: &patch[..footer], pos: 4 };
+
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    if source_size != rom.len(){
+        return Err(invalid("ROM size does not match the patch"));
+    }
+    check_target_size(target_size)?;
+
+    let mut target = rom.to_vec();
+    target.resize(target_size, 0);
+
+    let mut offset: usize = 0;
+    while reader.pos < footer{
+        offset = offset.checked_add(reader.varint()?).ok_or_else(|| invalid("Patch offset too large"))?;
+        loop{
+            let x = reader.byte()?;
+            if offset < target_size{
+                target[offset] ^= x;
+            }
+            offset = offset.checked_add(1).ok_or_else(|| invalid("Patch offset too large"))?;
+            if x == 0{
+                break;
+            }
+        }
+    }
+
+    verify_target(&target, target_crc)?;
+    Ok(target)
+}
+
+// BPS - copy commands from the source, the patch or earlier output
+fn apply_bps(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>>{
+    let (target_crc, footer) = verify_footer(rom, patch)?;
+    let mut reader = PatchReader{ data: &patch[..footer], pos: 4 };
+
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    let metadata_size = reader.varint()?;
+    reader.bytes(metadata_size)?;
+    if source_size != rom.len(){
+        return Err(invalid("ROM size does not match the patch"));
+    }
+    check_target_size(target_size)?;
+
+    let mut target = Vec::with_capacity(target_size);
+    let mut source_offset: isize = 0;
+    let mut target_offset: isize = 0;
+
+    // Relative offsets are stored as magnitude << 1 | sign
+    let relative = |reader: &mut PatchReader, base: &mut isize| -> io::Result<()>{
+        let data = reader.varint()?;
+        let delta = (data >> 1) as isize;
+        *base = base.checked_add(if (data & 1) != 0 {-delta} else {delta}).ok_or_else(|| invalid("Patch offset too large"))?;
+        if *base < 0{
+            return Err(invalid("Patch offset before the start of the ROM"));
+        }
+        Ok(())
+    };
+
+    while reader.pos < footer{
+        let data = reader.varint()?;
+        let len = (data >> 2) + 1;
+        if len > target_size - target.len(){
+            return Err(invalid("Patch writes past the end of the ROM"));
+        }
+        match data & 3{
+            // SourceRead
+            0 => {
+                let start = target.len();
+                target.extend_from_slice(rom.get(start..start + len).ok_or_else(|| invalid("Patch reads past the ROM"))?);
+            }
+            // TargetRead
+            1 => target.extend_from_slice(reader.bytes(len)?),
+            // SourceCopy
+            2 => {
+                relative(&mut reader, &mut source_offset)?;
+                let start = source_offset as usize;
+                target.extend_from_slice(rom.get(start..start + len).ok_or_else(|| invalid("Patch reads past the ROM"))?);
+                source_offset += len as isize;
+            }
+            // TargetCopy, may overlap the bytes being written so copy one at a time
+            _ => {
+                relative(&mut reader, &mut target_offset)?;
+                for _ in 0..len{
+                    let val = *target.get(target_offset as usize).ok_or_else(|| invalid("Patch reads past the output"))?;
+                    target.push(val);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if target.len() != target_size{
+        return Err(invalid("Patched ROM size does not match the patch"));
+    }
+    verify_target(&target, target_crc)?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn varint(mut n: usize, out: &mut Vec<u8>){
+        loop{
+            let x = (n & 0x7F) as u8;
+            n >>= 7;
+            if n == 0{
+                out.push(0x80 | x);
+                return;
+            }
+            out.push(x);
+            n -= 1;
+        }
+    }
+
+    // Appends the source, target and patch CRC-32s
+    fn with_footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8>{
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&crc32(target).to_le_bytes());
+        let patch_crc = crc32(&patch);
+        patch.extend_from_slice(&patch_crc.to_le_bytes());
+        patch
+    }
+
+    fn error_message(result: io::Result<Vec<u8>>) -> String{
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn crc32_check_value(){
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(&[]), 0);
+    }
+
+    #[test]
+    fn ips_copies_records_and_grows_the_rom(){
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0x00, 0x00, 0x02, 0x00, 0x03, 1, 2, 3]);
+        patch.extend_from_slice(&[0x00, 0x00, 0x09, 0x00, 0x01, 9]);
+        patch.extend_from_slice(b"EOF");
+
+        assert_eq!(apply_patch(&[0; 8], &patch).unwrap(), vec![0, 0, 1, 2, 3, 0, 0, 0, 0, 9]);
+    }
+
+    #[test]
+    fn ips_rle_record(){
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x04, 0xAA]);
+        patch.extend_from_slice(b"EOF");
+
+        assert_eq!(apply_patch(&[0; 6], &patch).unwrap(), vec![0, 0xAA, 0xAA, 0xAA, 0xAA, 0]);
+    }
+
+    #[test]
+    fn ips_truncation(){
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x01, 5]);
+        patch.extend_from_slice(b"EOF");
+        patch.extend_from_slice(&[0x00, 0x00, 0x04]);
+
+        assert_eq!(apply_patch(&[0; 8], &patch).unwrap(), vec![5, 0, 0, 0]);
+    }
+
+    #[test]
+    fn ips_cut_short(){
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x04, 1, 2]);
+
+        assert!(apply_patch(&[0; 8], &patch).is_err());
+    }
+
+    fn ups_patch(source: &[u8], target: &[u8]) -> Vec<u8>{
+        let mut patch = b"UPS1".to_vec();
+        varint(4, &mut patch);
+        varint(5, &mut patch);
+        // Skip 1, XOR one byte, then skip to offset 4 and XOR one more
+        varint(1, &mut patch);
+        patch.extend_from_slice(&[2 ^ 9, 0]);
+        varint(1, &mut patch);
+        patch.extend_from_slice(&[5, 0]);
+        with_footer(patch, source, target)
+    }
+
+    #[test]
+    fn ups_apply(){
+        let source = [1, 2, 3, 4];
+        let target = [1, 9, 3, 4, 5];
+
+        assert_eq!(apply_patch(&source, &ups_patch(&source, &target)).unwrap(), target.to_vec());
+    }
+
+    #[test]
+    fn ups_wrong_rom(){
+        let patch = ups_patch(&[1, 2, 3, 4], &[1, 9, 3, 4, 5]);
+
+        assert!(error_message(apply_patch(&[1, 2, 3, 5], &patch)).contains("different ROM"));
+    }
+
+    #[test]
+    fn ups_damaged_patch(){
+        let source = [1, 2, 3, 4];
+        let mut patch = ups_patch(&source, &[1, 9, 3, 4, 5]);
+        patch[8] ^= 0xFF;
+
+        assert!(error_message(apply_patch(&source, &patch)).contains("damaged"));
+    }
+
+    fn bps_patch(source: &[u8], target: &[u8]) -> Vec<u8>{
+        let mut patch = b"BPS1".to_vec();
+        varint(4, &mut patch);
+        varint(7, &mut patch);
+        varint(0, &mut patch);
+        varint(1 << 2, &mut patch);       // SourceRead 2
+        varint(1, &mut patch);            // TargetRead 1
+        patch.push(7);
+        varint((1 << 2) | 3, &mut patch); // TargetCopy 2 from output offset 2, overlapping
+        varint(2 << 1, &mut patch);
+        varint((1 << 2) | 2, &mut patch); // SourceCopy 2 from source offset 2
+        varint(2 << 1, &mut patch);
+        with_footer(patch, source, target)
+    }
+
+    #[test]
+    fn bps_apply(){
+        let source = [1, 2, 3, 4];
+        let target = [1, 2, 7, 7, 7, 3, 4];
+
+        assert_eq!(apply_patch(&source, &bps_patch(&source, &target)).unwrap(), target.to_vec());
+    }
+
+    #[test]
+    fn bps_wrong_rom(){
+        let patch = bps_patch(&[1, 2, 3, 4], &[1, 2, 7, 7, 7, 3, 4]);
+
+        assert!(error_message(apply_patch(&[4, 3, 2, 1], &patch)).contains("different ROM"));
+    }
+
+    #[test]
+    fn bps_target_mismatch(){
+        let source = [1, 2, 3, 4];
+        let patch = bps_patch(&source, &[1, 2, 7, 7, 7, 3, 5]);
+
+        assert!(error_message(apply_patch(&source, &patch)).contains("Patched ROM checksum mismatch"));
+    }
+
+    #[test]
+    fn varint_overflow(){
+        let mut data = vec![0; 11];
+        data.push(0x80);
+
+        assert!(PatchReader{ data: &data, pos: 0 }.varint().is_err());
+    }
+
+    #[test]
+    fn ups_huge_target_size(){
+        let source = [1, 2, 3, 4];
+        let mut patch = b"UPS1".to_vec();
+        varint(4, &mut patch);
+        varint(1 << 44, &mut patch);
+        let patch = with_footer(patch, &source, &source);
+
+        assert!(error_message(apply_patch(&source, &patch)).contains("too large"));
+    }
+
+    #[test]
+    fn ups_huge_offset(){
+        let source = [1, 2, 3, 4];
+        let mut patch = b"UPS1".to_vec();
+        varint(4, &mut patch);
+        varint(4, &mut patch);
+        for _ in 0..2{
+            varint(usize::MAX / 2 + 1, &mut patch);
+            patch.push(0);
+        }
+        let patch = with_footer(patch, &source, &source);
+
+        assert!(error_message(apply_patch(&source, &patch)).contains("too large"));
+    }
+
+    #[test]
+    fn bps_huge_target_size(){
+        let source = [1, 2, 3, 4];
+        let mut patch = b"BPS1".to_vec();
+        varint(4, &mut patch);
+        varint(1 << 44, &mut patch);
+        varint(0, &mut patch);
+        let patch = with_footer(patch, &source, &source);
+
+        assert!(error_message(apply_patch(&source, &patch)).contains("too large"));
+    }
+
+    #[test]
+    fn bps_huge_metadata(){
+        let source = [1, 2, 3, 4];
+        let mut patch = b"BPS1".to_vec();
+        varint(4, &mut patch);
+        varint(4, &mut patch);
+        varint(usize::MAX - 3, &mut patch);
+        let patch = with_footer(patch, &source, &source);
+
+        assert!(error_message(apply_patch(&source, &patch)).contains("ends unexpectedly"));
+    }
+
+    #[test]
+    fn bps_write_past_target_size(){
+        let source = [1, 2, 3, 4];
+        let mut patch = b"BPS1".to_vec();
+        varint(4, &mut patch);
+        varint(2, &mut patch);
+        varint(0, &mut patch);
+        varint(1, &mut patch);              // TargetRead 1
+        patch.push(7);
+        varint((1000 << 2) | 3, &mut patch); // TargetCopy 1001 from output offset 0
+        varint(0, &mut patch);
+        let patch = with_footer(patch, &source, &source);
+
+        assert!(error_message(apply_patch(&source, &patch)).contains("past the end"));
+    }
+}
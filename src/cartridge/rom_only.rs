@@ -0,0 +1,51 @@
+use super::mapper::{store, Mapper};
+
+// No MBC - 32 KiB of ROM mapped directly, optionally up to 8 KiB of unbanked RAM
+pub struct RomOnly{
+    rom: Vec<u8>,
+    ram: Vec<u8>
+}
+
+impl RomOnly{
+
+    pub fn new(rom: Vec<u8>, ram: Vec<u8>) -> RomOnly{
+        RomOnly{
+            rom: rom,
+            ram: ram
+        }
+    }
+}
+
+impl Mapper for RomOnly{
+
+    fn save_data(&self) -> &[u8]{
+        &self.ram
+    }
+
+    fn save_data_mut(&mut self) -> &mut [u8]{
+        &mut self.ram
+    }
+
+    fn read_rom(&self, loc: u16) -> u8{
+        self.rom[loc as usize]
+    }
+
+    fn write_rom(&mut self, loc: u16, val: u8){
+        println!("Writing to cartridge when no MBC is present: {:04X?} = {:02X?}", loc, val);
+    }
+
+    fn read_ram(&self, loc: u16) -> u8{
+        if self.ram.is_empty(){
+            return 0xFF;
+        }
+        self.ram[(loc - 0xA000) as usize % self.ram.len()]
+    }
+
+    fn write_ram(&mut self, loc: u16, val: u8) -> bool{
+        if self.ram.is_empty(){
+            return false;
+        }
+        let len = self.ram.len();
+        store(&mut self.ram, (loc - 0xA000) as usize % len, val)
+    }
+}
@@ -0,0 +1,243 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const CYCLES_PER_SECOND: u32 = 4194304;
+
+// RTC footer appended to .sav files by VBA-M and BGB: seconds, minutes, hours, days low and days high as
+// 32 bit words, the same five latched values, then the Unix time of the save as 64 bits.
+// Older VBA-M saves use a 32 bit timestamp.
+pub const RTC_FOOTER_SIZE: usize = 48;
+pub const RTC_FOOTER_SIZE_OLD: usize = 44;
+
+pub fn encode_footer(current: [u32; 5], latched: [u32; 5], timestamp: u64) -> Vec<u8>{
+    let mut footer = Vec::with_capacity(RTC_FOOTER_SIZE);
+    for word in current.iter().chain(latched.iter()){
+        footer.extend_from_slice(&word.to_le_bytes());
+    }
+    footer.extend_from_slice(&timestamp.to_le_bytes());
+    footer
+}
+
+pub fn decode_footer(footer: &[u8]) -> Option<([u32; 5], [u32; 5], u64)>{
+    if footer.len() != RTC_FOOTER_SIZE && footer.len() != RTC_FOOTER_SIZE_OLD{
+        return None;
+    }
+    let word = |i: usize| u32::from_le_bytes([footer[i * 4], footer[i * 4 + 1], footer[i * 4 + 2], footer[i * 4 + 3]]);
+    let mut current = [0; 5];
+    let mut latched = [0; 5];
+    for i in 0..5{
+        current[i] = word(i);
+        latched[i] = word(5 + i);
+    }
+    let timestamp = if footer.len() == RTC_FOOTER_SIZE{
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&footer[40..48]);
+        u64::from_le_bytes(bytes)
+    } else{
+        word(10) as u64
+    };
+    Some((current, latched, timestamp))
+}
+
+pub fn unix_time(time: SystemTime) -> u64{
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+// Seconds between a saved timestamp and now, 0 if the save is from the future
+pub fn seconds_since(timestamp: u64) -> u64{
+    unix_time(SystemTime::now()).saturating_sub(timestamp)
+}
+
+// Where the clock gets its notion of elapsed time from
+#[derive(Clone, Copy, PartialEq)]
+pub enum RtcClock{
+    Host,     // Wall clock time, keeps running while the emulator is paused or fast forwarded
+    Emulated  // Emulated CPU cycles
+}
+
+// MBC3 real time clock
+pub struct Rtc{
+    clock: RtcClock,
+    seconds: u8,   // 0x08
+    minutes: u8,   // 0x09
+    hours: u8,     // 0x0A
+    days: u16,     // 0x0B + bit 0 of 0x0C
+    halted: bool,  // Bit 6 of 0x0C
+    day_carry: bool, // Bit 7 of 0x0C
+    latched: [u8; 5],
+    latch_reg: u8, // Last value written to 0x6000 - 0x7FFF, latching happens on 0x00 -> 0x01
+    cycles: u32,   // Emulated clock: cycles into the current second
+    last_sync: SystemTime // Host clock: time the registers were last brought up to date
+}
+
+impl Rtc{
+
+    pub fn new(clock: RtcClock) -> Rtc{
+        Rtc{
+            clock: clock,
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            days: 0,
+            halted: false,
+            day_carry: false,
+            latched: [0; 5],
+            latch_reg: 0xFF,
+            cycles: 0,
+            last_sync: SystemTime::now()
+        }
+    }
+
+    pub fn set_clock(&mut self, clock: RtcClock){
+        self.sync();
+        self.clock = clock;
+        self.cycles = 0;
+        self.last_sync = SystemTime::now();
+    }
+
+    pub fn step(&mut self, m_cycles: u8){
+        if self.clock != RtcClock::Emulated || self.halted{
+            return;
+        }
+        self.cycles += m_cycles as u32 * 4;
+        while self.cycles >= CYCLES_PER_SECOND{
+            self.cycles -= CYCLES_PER_SECOND;
+            self.tick_second();
+        }
+    }
+
+    // Catch the registers up with the host clock
+    fn sync(&mut self){
+        if self.clock != RtcClock::Host{
+            return;
+        }
+        let now = SystemTime::now();
+        let elapsed = match now.duration_since(self.last_sync){
+            Ok(elapsed) => elapsed,
+            Err(_) => { self.last_sync = now; return; } // Host clock went backwards
+        };
+        if self.halted{
+            self.last_sync = now;
+            return;
+        }
+        self.last_sync += std::time::Duration::from_secs(elapsed.as_secs());
+        self.advance(elapsed.as_secs());
+    }
+
+    fn advance(&mut self, mut secs: u64){
+        // Out of range values ( e.g seconds = 62 ) roll over without carrying, step through those one at a time
+        while secs > 0 && (self.seconds >= 60 || self.minutes >= 60 || self.hours >= 24){
+            self.tick_second();
+            secs -= 1;
+        }
+        if secs == 0{
+            return;
+        }
+
+        let total = self.seconds as u64 + self.minutes as u64 * 60 + self.hours as u64 * 3600 + self.days as u64 * 86400 + secs;
+        self.seconds = (total % 60) as u8;
+        self.minutes = (total / 60 % 60) as u8;
+        self.hours = (total / 3600 % 24) as u8;
+        let days = total / 86400;
+        if days > 0x1FF{
+            self.day_carry = true;
+        }
+        self.days = (days & 0x1FF) as u16;
+    }
+
+    fn tick_second(&mut self){
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60{
+            return;
+        }
+        self.seconds = 0;
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60{
+            return;
+        }
+        self.minutes = 0;
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24{
+            return;
+        }
+        self.hours = 0;
+        self.days += 1;
+        if self.days > 0x1FF{
+            self.days = 0;
+            self.day_carry = true;
+        }
+    }
+
+    fn day_high(&self) -> u8{
+        ((self.days >> 8) as u8 & 0x01) | ((self.halted as u8) << 6) | ((self.day_carry as u8) << 7)
+    }
+
+    // The registers are as of last_sync, which is what the timestamp records for the host clock
+    pub fn footer(&self) -> Vec<u8>{
+        let timestamp = match self.clock{
+            RtcClock::Host => unix_time(self.last_sync),
+            RtcClock::Emulated => unix_time(SystemTime::now())
+        };
+        let current = [self.seconds as u32, self.minutes as u32, self.hours as u32, self.days as u32 & 0xFF, self.day_high() as u32];
+        let mut latched = [0; 5];
+        for (i, val) in self.latched.iter().enumerate(){
+            latched[i] = *val as u32;
+        }
+        encode_footer(current, latched, timestamp)
+    }
+
+    // Restores the registers and catches up with the time that passed since the save was written
+    pub fn load_footer(&mut self, footer: &[u8]){
+        let (current, latched, timestamp) = match decode_footer(footer){
+            Some(rtc) => rtc,
+            None => return
+        };
+        self.seconds = current[0] as u8 & 0x3F;
+        self.minutes = current[1] as u8 & 0x3F;
+        self.hours = current[2] as u8 & 0x1F;
+        self.days = (current[3] & 0xFF) as u16 | (((current[4] & 0x01) as u16) << 8);
+        self.halted = (current[4] & 0x40) != 0;
+        self.day_carry = (current[4] & 0x80) != 0;
+        for (i, val) in latched.iter().enumerate(){
+            self.latched[i] = *val as u8;
+        }
+        self.cycles = 0;
+        self.last_sync = SystemTime::now();
+        if !self.halted{
+            self.advance(seconds_since(timestamp));
+        }
+    }
+
+    pub fn write_latch(&mut self, val: u8){
+        if self.latch_reg == 0x00 && val == 0x01{
+            self.sync();
+            self.latched = [self.seconds, self.minutes, self.hours, self.days as u8, self.day_high()];
+        }
+        self.latch_reg = val;
+    }
+
+    // reg: 0x08 - 0x0C
+    pub fn read(&self, reg: u8) -> u8{
+        let unused_bits = [0xC0, 0xC0, 0xE0, 0x00, 0x3E];
+        self.latched[(reg - 0x08) as usize] | unused_bits[(reg - 0x08) as usize]
+    }
+
+    pub fn write(&mut self, reg: u8, val: u8){
+        self.sync();
+        match reg{
+            0x08 => {
+                // Writing the seconds also resets the sub second divider
+                self.seconds = val & 0x3F;
+                self.cycles = 0;
+                self.last_sync = SystemTime::now();
+            }
+            0x09 => self.minutes = val & 0x3F,
+            0x0A => self.hours = val & 0x1F,
+            0x0B => self.days = (self.days & 0x100) | val as u16,
+            _ => {
+                self.days = (self.days & 0xFF) | (((val & 0x01) as u16) << 8);
+                self.halted = (val & 0x40) != 0;
+                self.day_carry = (val & 0x80) != 0;
+            }
+        }
+    }
+}
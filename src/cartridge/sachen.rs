@@ -0,0 +1,89 @@
+use super::header::{LOGO_START, NINTENDO_LOGO};
+use super::mapper::Mapper;
+
+// Sachen MMC1 / MMC2
+// Both start locked, scrambling header reads so the boot ROM finds a Nintendo logo hidden at 0x0184.
+// They unlock once the boot ROM hands over ( MMC2 adds a second stage for CGB ), since the boot ROM
+// is not run here the mapper starts unlocked and both behave the same from then on.
+pub struct Sachen{
+    rom: Vec<u8>,
+    base_bank: u8, // 0x0000 - 0x1FFF : Outer bank
+    rom_bank: u8,  // 0x2000 - 0x3FFF
+    bank_mask: u8, // 0x4000 - 0x5FFF : Bits taken from base_bank instead of rom_bank
+    rom_bitmask: u8
+}
+
+// Locked address translation : A7 forced high, A0 <-> A6 and A1 <-> A4 swapped
+fn scramble(loc: u16) -> u16{
+    let bit = |n: u16| (loc >> n) & 1;
+    (loc & 0xFFAC) | 0x80 | (bit(6)) | (bit(4) << 1) | (bit(1) << 4) | (bit(0) << 6)
+}
+
+// The real logo at 0x0104 is Sachen's, Nintendo's is only visible through the scrambled addresses
+pub fn has_scrambled_logo(rom: &[u8]) -> bool{
+    rom[LOGO_START..LOGO_START + 48] != NINTENDO_LOGO &&
+        (0..48).all(|i| rom[scramble((LOGO_START + i) as u16) as usize] == NINTENDO_LOGO[i])
+}
+
+impl Sachen{
+
+    pub fn new(rom: Vec<u8>) -> Sachen{
+        let rom_bitmask = ((rom.len() / 0x4000 - 1) & 0xFF) as u8;
+
+        Sachen{
+            rom: rom,
+            base_bank: 0,
+            rom_bank: 1,
+            bank_mask: 0,
+            rom_bitmask: rom_bitmask
+        }
+    }
+}
+
+impl Mapper for Sachen{
+
+    fn read_rom(&self, loc: u16) -> u8{
+        match loc{
+            0x0000..=0x3FFF => {
+                let bank = (self.base_bank & self.bank_mask & self.rom_bitmask) as usize;
+                self.rom[bank * 0x4000 + loc as usize]
+            }
+            _ => {
+                let bank = ((self.base_bank & self.bank_mask) | (self.rom_bank & !self.bank_mask)) & self.rom_bitmask;
+                self.rom[bank as usize * 0x4000 + (loc - 0x4000) as usize]
+            }
+        }
+    }
+
+    fn write_rom(&mut self, loc: u16, val: u8){
+        // The outer bank and mask can only be changed while the inner bank has bits 4 and 5 set
+        let outer_writable = (self.rom_bank & 0x30) == 0x30;
+        match loc{
+            0x0000..=0x1FFF => {
+                if outer_writable{
+                    self.base_bank = val;
+                }
+            }
+            0x2000..=0x3FFF => {
+                self.rom_bank = match val{
+                    0x0 => 1,
+                    bank => bank
+                };
+            }
+            0x4000..=0x5FFF => {
+                if outer_writable{
+                    self.bank_mask = val;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, _loc: u16) -> u8{
+        0xFF
+    }
+
+    fn write_ram(&mut self, _loc: u16, _val: u8) -> bool{
+        false
+    }
+}
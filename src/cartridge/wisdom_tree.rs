@@ -0,0 +1,43 @@
+use super::mapper::Mapper;
+
+// Wisdom Tree - switches the whole 0x0000 - 0x7FFF window in 32 KiB banks
+// The bank number comes from the low byte of the address written to, not the value
+pub struct WisdomTree{
+    rom: Vec<u8>,
+    rom_bank: u8,
+    rom_bitmask: u8
+}
+
+impl WisdomTree{
+
+    pub fn new(rom: Vec<u8>) -> WisdomTree{
+        let rom_bitmask = ((rom.len() / 0x8000).max(1) - 1) as u8;
+
+        WisdomTree{
+            rom: rom,
+            rom_bank: 0,
+            rom_bitmask: rom_bitmask
+        }
+    }
+}
+
+impl Mapper for WisdomTree{
+
+    fn read_rom(&self, loc: u16) -> u8{
+        self.rom[(self.rom_bank & self.rom_bitmask) as usize * 0x8000 + loc as usize]
+    }
+
+    fn write_rom(&mut self, loc: u16, _val: u8){
+        if loc <= 0x3FFF{
+            self.rom_bank = loc as u8;
+        }
+    }
+
+    fn read_ram(&self, _loc: u16) -> u8{
+        0xFF
+    }
+
+    fn write_ram(&mut self, _loc: u16, _val: u8) -> bool{
+        false
+    }
+}
@@ -0,0 +1,60 @@
+use crate::cartridge::RtcClock;
+
+// Command line options
+pub struct Config{
+    pub rom_path: String,
+    pub rtc_clock: RtcClock,
+    pub camera_image: Option<String>,
+    pub save_dir: Option<String>,
+    pub patch: Option<String>,
+    pub sprite_limit: bool,
+    pub pixel_fifo: bool,
+    pub palette: Option<String>,      // Preset name or 4 hex colours, used for BG and both OBJ palettes
+    pub bg_palette: Option<String>,
+    pub obj0_palette: Option<String>,
+    pub obj1_palette: Option<String>
+}
+
+impl Config{
+
+    // Fails on unknown options and options missing their value
+    pub fn from_args(args: &[String]) -> Result<Config, String>{
+        let mut config = Config{
+            rom_path: String::new(),
+            rtc_clock: RtcClock::Host,
+            camera_image: None,
+            save_dir: None,
+            patch: None,
+            sprite_limit: true,
+            pixel_fifo: false,
+            palette: None,
+            bg_palette: None,
+            obj0_palette: None,
+            obj1_palette: None
+        };
+
+        let mut args = args.iter().skip(1);
+        while let Some(arg) = args.next(){
+            match arg.as_str(){
+                "--rtc-emulated" => config.rtc_clock = RtcClock::Emulated,
+                "--camera-image" => config.camera_image = Some(value(arg, &mut args)?),
+                "--save-dir" => config.save_dir = Some(value(arg, &mut args)?),
+                "--patch" => config.patch = Some(value(arg, &mut args)?),
+                "--no-sprite-limit" => config.sprite_limit = false,
+                "--fifo" => config.pixel_fifo = true,
+                "--palette" => config.palette = Some(value(arg, &mut args)?),
+                "--bg-palette" => config.bg_palette = Some(value(arg, &mut args)?),
+                "--obj0-palette" => config.obj0_palette = Some(value(arg, &mut args)?),
+                "--obj1-palette" => config.obj1_palette = Some(value(arg, &mut args)?),
+                _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+                _ => config.rom_path = arg.clone()
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn value<'a>(flag: &str, args: &mut impl Iterator<Item = &'a String>) -> Result<String, String>{
+    args.next().cloned().ok_or_else(|| format!("{} needs a value", flag))
+}
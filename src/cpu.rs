@@ -28,6 +28,10 @@ impl<'a> CPU<'a>{
         return cpu;
     }
 
+    pub fn mmu(&mut self) -> &mut MMU{
+        self.mmu
+    }
+
     pub fn cpu_step(&mut self) -> u8{
         let m_cycles;
         if self.ie_change{
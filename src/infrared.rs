@@ -0,0 +1,16 @@
+// Infrared port, implemented by whatever sits on the other side of the LED ( another emulator, a loopback, ... )
+pub trait Infrared{
+    fn set_led(&mut self, on: bool);
+    fn light_detected(&self) -> bool;
+}
+
+// Nothing in range, the receiver never sees light
+pub struct NoInfrared;
+
+impl Infrared for NoInfrared{
+    fn set_led(&mut self, _on: bool){}
+
+    fn light_detected(&self) -> bool{
+        false
+    }
+}
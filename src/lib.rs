@@ -0,0 +1,13 @@
+// Emulator core, main.rs is the minifb frontend around it
+pub mod cpu;
+pub mod mmu;
+pub mod registers;
+pub mod instructions;
+pub mod timer;
+pub mod ppu;
+pub mod cartridge;
+pub mod util;
+pub mod config;
+pub mod infrared;
+pub mod save;
+pub mod palette;
@@ -1,15 +1,12 @@
-mod cpu;
-mod mmu;
-mod registers;
-mod instructions;
-mod timer;
-mod ppu;
-mod cartridge;
-mod util;
-
-use cpu::CPU;
-use mmu::MMU;
+use rust_gb::cpu::CPU;
+use rust_gb::mmu::MMU;
+use rust_gb::cartridge::{Cartridge, load_patched_rom, png_image_provider};
+use rust_gb::config::Config;
+use rust_gb::palette::{self, Palette};
+use rust_gb::save;
 use std::env;
+use std::path::Path;
+use std::process;
 use std::time::{Instant};
 
 extern crate minifb;
@@ -19,24 +16,151 @@ use minifb::{Key, Window, WindowOptions};
 const WIDTH: usize = 160;
 const HEIGHT: usize = 144;
 
+const CYCLES_PER_FRAME: u32 = 17556; // M-cycles
+
+// Bit of each Game Boy button in MMU::set_buttons
+const KEY_MAP: [(u8, Key); 8] = [(0, Key::Left), (1, Key::Right), (2, Key::Up), (3, Key::Down), (4, Key::A), (5, Key::B), (6, Key::Enter), (7, Key::Space)];
+
 fn main() {
     let args: Vec<String> = env::args().collect();
-    
-    let mut mmu = MMU::new(&args[1]);
+    let config = match Config::from_args(&args){
+        Ok(config) if !config.rom_path.is_empty() => config,
+        Ok(_) => usage(),
+        Err(e) => {
+            eprintln!("{}", e);
+            usage()
+        }
+    };
+
+    let loaded = load_patched_rom(&config.rom_path, config.patch.as_deref().map(Path::new)).and_then(Cartridge::from_bytes);
+    let mut cartridge = match loaded{
+        Ok(cartridge) => cartridge,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    cartridge.set_rtc_clock(config.rtc_clock);
+    if let Some(path) = &config.camera_image{
+        match png_image_provider(path){
+            Ok(provider) => cartridge.set_camera_image_provider(provider),
+            Err(e) => println!("Could not load camera image {}: {}", path, e)
+        }
+    }
+
+    let save_path = save::save_path(&config.rom_path, config.save_dir.as_deref());
+    let battery = cartridge.has_battery();
+    if battery{
+        if let Some(data) = save::load(&save_path){
+            println!("Loaded save file {}", save_path.display());
+            cartridge.load_save_data(&data);
+        }
+    }
+
+    let mut window = Window::new(
+        "Rust-gb",
+        WIDTH,
+        HEIGHT,
+        WindowOptions::default(),
+    ).unwrap_or_else(|e|{
+        panic!("{}", e)
+    });
+
+    let mut mmu = MMU::new(cartridge);
+    mmu.set_sprite_limit(config.sprite_limit);
+    mmu.set_pixel_fifo(config.pixel_fifo);
+    let default_palette = palette_option(config.palette.as_deref(), palette::GREY);
+    mmu.set_palettes(
+        palette_option(config.bg_palette.as_deref(), default_palette),
+        palette_option(config.obj0_palette.as_deref(), default_palette),
+        palette_option(config.obj1_palette.as_deref(), default_palette)
+    );
     let mut cpu = CPU::new(&mut mmu);
 
-    let mut cycles:u16 = 0;
+    let mut cycles: u32 = 0;
+    let mut frames: u32 = 0;
     let mut now = Instant::now();
-    loop
+    while window.is_open()
     {
-            cycles = cycles.wrapping_add(cpu.cpu_step() as u16);
-            if cycles >= 17496 {
-                cycles = 0;
-                while now.elapsed().as_millis() < (16.67 as u128){
-                    
+            cycles += cpu.cpu_step() as u32;
+
+            let frame_done = match cpu.mmu().take_frame(){
+                Some(frame) => {
+                    window.update_with_buffer(frame, WIDTH, HEIGHT).unwrap();
+                    true
                 }
-                now = Instant::now();
+                None => false
+            };
+            // No frames complete while the LCD is off, keep the window responsive anyway
+            if !frame_done && cycles < CYCLES_PER_FRAME{
+                continue;
+            }
+            if !frame_done{
+                window.update();
+            }
+
+            cycles = 0;
+            cpu.mmu().set_buttons(joypad_state(&window));
+            let (tilt_x, tilt_y) = tilt_from_keys(&window);
+            cpu.mmu().set_tilt(tilt_x, tilt_y);
+
+            // Flush the save about once a second while the game keeps writing to it
+            frames = frames.wrapping_add(1);
+            if battery && frames % 60 == 0 && cpu.mmu().cartridge().take_ram_dirty(){
+                flush_save(cpu.mmu().cartridge(), &save_path);
+            }
+
+            while now.elapsed().as_millis() < (16.67 as u128){
+                
             }
+            now = Instant::now();
             
     }
+
+    if battery{
+        flush_save(cpu.mmu().cartridge(), &save_path);
+    }
+}
+
+fn joypad_state(window: &Window) -> u8{
+    let mut state = 0x00;
+    for (bit_pos, key) in KEY_MAP.iter(){
+        if window.is_key_down(*key){
+            state |= 1 << bit_pos;
+        }
+    }
+    state
+}
+
+// I, J, K and L tilt the console one g forward, left, back and right
+fn tilt_from_keys(window: &Window) -> (f32, f32){
+    let mut x = 0.0;
+    let mut y = 0.0;
+    if window.is_key_down(Key::J){ x -= 1.0; }
+    if window.is_key_down(Key::L){ x += 1.0; }
+    if window.is_key_down(Key::I){ y -= 1.0; }
+    if window.is_key_down(Key::K){ y += 1.0; }
+    (x, y)
+}
+
+fn usage() -> !{
+    eprintln!("Usage: rust-gb [options] <path_to_game>");
+    process::exit(1);
+}
+
+// Falls back to the default when the option is missing or not a valid palette
+fn palette_option(option: Option<&str>, default: Palette) -> Palette{
+    match option{
+        Some(s) => palette::parse(s).unwrap_or_else(||{
+            println!("Unknown palette {}, expected a preset or 4 comma separated hex colours", s);
+            default
+        }),
+        None => default
+    }
+}
+
+fn flush_save(cartridge: &Cartridge, save_path: &Path){
+    if let Err(e) = save::write(save_path, &cartridge.save_data()){
+        println!("Could not write save file {}: {}", save_path.display(), e);
+    }
 }
@@ -1,12 +1,9 @@
-use std::{collections::HashMap, fs::File, io::Read};
+use std::{fs::File, io::Read};
 
-use crate::{cartridge, timer::Timer, util::{set_bit_u8, test_bit_u8}};
+use crate::{cartridge, timer::Timer, util::test_bit_u8};
 use crate::ppu::PPU;
+use crate::palette::Palette;
 use crate::cartridge::Cartridge;
-use minifb::{Key, Window, WindowOptions};
-
-const WIDTH: usize = 160;
-const HEIGHT: usize = 144;
 
 const VRAM_START: u16 = 0x8000;
 const VRAM_END: u16 = 0x9fff;
@@ -21,21 +18,21 @@ pub struct MMU{
     serial_interrupt: u8,
     cartridge: Cartridge,
     joypad: u8,
-    joypadMap: HashMap<u8, Key> 
+    buttons: u8 // Pressed buttons, set by the frontend. 0 - Left, 1 - Right, 2 - Up, 3 - Down, 4 - A, 5 - B, 6 - Select, 7 - Start
 }
 
 // Need to implement custom get and set operations for different mem regions
 impl MMU{
 
-    pub fn new(file: &String) -> MMU{
+    pub fn new(cartridge: Cartridge) -> MMU{
         let mut mmu = MMU{
             mem: [0;65536],
             timer: Timer::new(),
             ppu: PPU::new(),
             serial_interrupt: 0,
-            cartridge: Cartridge::new(file),
+            cartridge: cartridge,
             joypad: 0xFF, // lower 4 bits : directional keys, upper 4 bits : Select, Start , A and B
-            joypadMap : [(0, Key::Left), (1, Key::Right), (2,Key::Up), (3, Key::Down), (4, Key::A), (5,Key::B), (6, Key::Enter), (7, Key::Space)].iter().cloned().collect()
+            buttons: 0x00
         };
         mmu.write_byte(0xFF0F, 0xE0);
         return mmu;   
@@ -44,17 +41,18 @@ impl MMU{
     pub fn step(&mut self, m_cycles: u8){
         self.timer.step_cycle(m_cycles);
         self.ppu.ppu_step(m_cycles);
+        self.cartridge.step(m_cycles);
     }
 
     pub fn read_byte(&self, loc: u16) -> u8{
 
         match loc{
-            0x0000..=0x7FFF =>{ // Check the end value ( inclusive or exclusive )
+            0x0000..=0x7FFF | 0xA000..=0xBFFF =>{ // Check the end value ( inclusive or exclusive )
                 self.cartridge.read_byte(loc)
             }
 
 
-            0xFE00..=0xFE9F | 0xFF40 | 0xFF42 | 0xFF43 | 0xFF44| 0xFF45 |0xFF47..=0xFF49 | VRAM_START..=VRAM_END => {
+            0xFE00..=0xFE9F | 0xFF40..=0xFF45 | 0xFF47..=0xFF4B | VRAM_START..=VRAM_END => {
                 self.ppu.read_byte(loc)
             }
 
@@ -105,8 +103,8 @@ impl MMU{
 
     fn update_interrupts(&mut self, new_if: u8){
         self.timer.write_byte(0xFF0F, new_if);
+        self.ppu.write_byte(0xFF0F, new_if);
         
-        // Only supporting seral and timer interrupts for now
         if new_if & (1 << 3) > 0 {
             self.serial_interrupt = 1;
         }
@@ -118,10 +116,10 @@ impl MMU{
 
     pub fn write_byte(&mut self, loc: u16, val: u8){
         match loc{
-            0x0000..=0x7FFF => {
+            0x0000..=0x7FFF | 0xA000..=0xBFFF => {
                 self.cartridge.write_byte(loc, val);
             }
-            0xFE00..=0xFE9F | 0xFF40 | 0xFF42 | 0xFF43 | 0xFF44  | 0xFF45| 0xFF47..=0xFF49 | VRAM_START..=VRAM_END => {
+            0xFE00..=0xFE9F | 0xFF40..=0xFF45 | 0xFF47..=0xFF4B | VRAM_START..=VRAM_END => {
                 self.ppu.write_byte(loc, val);
             }
             JOYPAD_REG => {
@@ -145,14 +143,37 @@ impl MMU{
         self.mem[(loc + 1) as usize] = (val >> 8) as u8;
     }
 
+    pub fn cartridge(&mut self) -> &mut Cartridge{
+        &mut self.cartridge
+    }
+
+    pub fn take_frame(&mut self) -> Option<&[u32]>{
+        self.ppu.take_frame()
+    }
+
+    pub fn set_sprite_limit(&mut self, enabled: bool){
+        self.ppu.set_sprite_limit(enabled);
+    }
+
+    pub fn set_pixel_fifo(&mut self, enabled: bool){
+        self.ppu.set_pixel_fifo(enabled);
+    }
+
+    pub fn set_palettes(&mut self, bg: Palette, obj0: Palette, obj1: Palette){
+        self.ppu.set_palettes(bg, obj0, obj1);
+    }
+
+    pub fn set_buttons(&mut self, buttons: u8){
+        self.buttons = buttons;
+    }
+
+    // Accelerometer input for MBC7 carts, x and y are in g
+    pub fn set_tilt(&mut self, x: f32, y: f32){
+        self.cartridge.set_tilt(x, y);
+    }
+
     fn get_joypad_state(& self) -> u8{
-        let mut state = 0x00;
-        for (bit_pos, key) in &self.joypadMap{
-            if self.ppu.window.is_key_down(*key){
-                set_bit_u8(&mut state, *bit_pos);
-            }
-        }
-        state
+        self.buttons
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,38 @@
+// Colours the four DMG shades are shown as, from colour 0 ( lightest ) to colour 3, in minifb's 0RGB format
+pub type Palette = [u32; 4];
+
+pub const GREY: Palette = [0x00FFFFFF, 0x00A0A0A0, 0x00555555, 0x00000000];
+pub const DMG: Palette = [0x009BBC0F, 0x008BAC0F, 0x00306230, 0x000F380F];
+pub const POCKET: Palette = [0x00C4CFA1, 0x008B956D, 0x004D533C, 0x001F1F1F];
+pub const LIGHT: Palette = [0x0000B581, 0x00009A71, 0x0000694A, 0x00004F3B]; // Game Boy Light backlight
+pub const HIGH_CONTRAST: Palette = [0x00FFFFFF, 0x00C0C0C0, 0x00404040, 0x00000000];
+pub const COLOR_BLIND: Palette = [0x00FFFFFF, 0x00E69F00, 0x000072B2, 0x00000000]; // Okabe-Ito orange and blue
+
+pub const PRESETS: [(&str, Palette); 6] = [
+    ("grey", GREY),
+    ("dmg", DMG),
+    ("pocket", POCKET),
+    ("light", LIGHT),
+    ("high-contrast", HIGH_CONTRAST),
+    ("color-blind", COLOR_BLIND)
+];
+
+// A preset name or four comma separated RGB hex colours, e.g "e0f8d0,88c070,346856,081820"
+pub fn parse(s: &str) -> Option<Palette>{
+    if let Some((_, palette)) = PRESETS.iter().find(|(name, _)| name.eq_ignore_ascii_case(s)){
+        return Some(*palette);
+    }
+
+    let colors: Vec<&str> = s.split(',').map(|c| c.trim().trim_start_matches('#')).collect();
+    if colors.len() != 4{
+        return None;
+    }
+    let mut palette = [0; 4];
+    for (i, color) in colors.iter().enumerate(){
+        if color.len() != 6{
+            return None;
+        }
+        palette[i] = u32::from_str_radix(color, 16).ok()?;
+    }
+    Some(palette)
+}
@@ -1,13 +1,12 @@
-extern crate minifb;
-
 use std::{borrow::Borrow, f32::consts::LOG2_E};
 use crate::util::*;
+use crate::palette::{self, Palette};
 
+mod fifo;
+use fifo::PixelFifo;
 
-use minifb::{Window, WindowOptions};
 
 const WIDTH: usize = 160;
-const HEIGHT: usize = 144;
 
 const OAM_CYCLES: u16 = 80;
 const DRAW_CYCLES: u16 = 172;
@@ -28,25 +27,31 @@ const BG_WIND_ENABLE_BIT_POS: u8  = 0;
 
 // ---------------LCD STAT Bit Positions ----------
 const STAT_LYC_BIT_POS: u8 = 6; 
+const STAT_OAM_BIT_POS: u8 = 5;
+const STAT_VBLANK_BIT_POS: u8 = 4;
+const STAT_HBLANK_BIT_POS: u8 = 3;
 const STAT_COINCIDENCE_BIT_POS:u8 = 2;
+const STAT_MODE_MASK: u8 = 0x03;
+const STAT_WRITE_MASK: u8 = 0x78; // Only the interrupt enables can be written
 
 
 //-------------------------------------------------
 
-const WHITE: u32 = 0x00ffffff;
 
+// Values are what STAT bits 0 - 1 report
+#[derive(Clone, Copy, PartialEq)]
 enum PPU_MODE{
-    OAM,
-    HBLANK,
-    VBLANK,
-    DRAW
+    OAM = 2,
+    HBLANK = 0,
+    VBLANK = 1,
+    DRAW = 3
 }
 
 pub struct PPU {
     ppu_clock: u16,
     mode: PPU_MODE,
     back_buffer: [u32; 160*144],
-    pub window: Window,
+    frame_ready: bool, // Set when a frame is complete in back_buffer, cleared once the frontend takes it
     vram: [u8; 8192],
     oam_mem: [u8; 160], // 0xFE00 - 0xFE9F
 
@@ -59,9 +64,24 @@ pub struct PPU {
     bg_pallete: u8,    // Addr: 0xFF47
     obj_pallete_1: u8, // Addr: 0xFF48
     obj_pallete_2: u8, // Addr: 0xFF49
-
-    colors: [u32; 4],
-    sprite_line_data:[u8; 10], // Used to hold sprite data for current line
+    wy: u8,            // Addr: 0xFF4A
+    wx: u8,            // Addr: 0xFF4B - Window X position + 7
+    window_line: u8,        // Internal window line counter, only advances on lines the window was drawn on
+    window_y_triggered: bool, // LY matched WY at some point this frame
+    window_full_line: bool,   // WX was 166 on the previous window line, the window spans this whole line
+
+    bg_colors: Palette,
+    obj0_colors: Palette,
+    obj1_colors: Palette,
+    sprite_line_data:[u8; 40], // OAM indices of the sprites selected for the current line, in priority order
+    sprite_count: usize,       // Number of entries used in sprite_line_data
+    sprite_limit: bool,        // Hardware only shows 10 sprites per line, turning this off removes flicker
+    bg_line: [u8; 160],        // BG / window colour numbers ( before the palette ) of the current line, for sprite priority
+    blank_frame: bool,         // First frame after the LCD is turned on is not shown
+    pixel_fifo: bool,          // Render with the pixel FIFO instead of a whole line at the end of mode 3
+    fifo: PixelFifo,
+    hblank_cycles: u16,        // Mode 0 length of the current line, shorter when mode 3 ran long
+    stat_line: bool,           // OR of the enabled STAT sources, the interrupt is requested on its rising edge
 
     pub stat_interrupt: u8,
     pub vblank_interrupt: u8,
@@ -74,26 +94,11 @@ impl PPU {
             ppu_clock: 0,
             mode: PPU_MODE::VBLANK, // Check : TODO
             back_buffer : [0x00ffffffff; 160*144],
-            window : Window::new(
-                "Rust-gb",
-                WIDTH,
-                HEIGHT,
-                WindowOptions::default(),
-            ).unwrap_or_else(|e|{
-                panic!("{}", e)
-            }),
-            /*debug_window : Window::new(
-                "Tile Map",
-                255,
-                255,
-                WindowOptions::default(),
-            ).unwrap_or_else(|e|{
-                panic!("{}", e)
-            }),*/
+            frame_ready: false,
             vram: [0; 8192],
             oam_mem: [0; 160],
             lcd_control: 0x91,
-            lcd_stat: 0xff,
+            lcd_stat: 0x85,
             scx: 0,
             scy: 0,
             ly: 0,
@@ -101,8 +106,23 @@ impl PPU {
             bg_pallete: 0xFC,
             obj_pallete_1: 0xFF,
             obj_pallete_2: 0xFF,
-            colors: [0x00ffffff ,0x00A0A0A0,0x00555555, 0 ], // Minifb pixel data format
-            sprite_line_data: [0; 10],
+            wy: 0,
+            wx: 0,
+            window_line: 0,
+            window_y_triggered: false,
+            window_full_line: false,
+            bg_colors: palette::GREY,
+            obj0_colors: palette::GREY,
+            obj1_colors: palette::GREY,
+            sprite_line_data: [0; 40],
+            sprite_count: 0,
+            sprite_limit: true,
+            bg_line: [0; 160],
+            blank_frame: false,
+            pixel_fifo: false,
+            fifo: PixelFifo::new(),
+            hblank_cycles: HBLANK_CYCLES,
+            stat_line: false,
             stat_interrupt: 0,
             vblank_interrupt:0, 
         }
@@ -110,152 +130,203 @@ impl PPU {
 
     fn render_background_line(&mut self){
         if test_bit_u8(self.lcd_control, BG_WIND_ENABLE_BIT_POS)  {
-            let background_map_line = self.ly.wrapping_add(self.scy+1); // Check the +1
-        
+            // Line in the 256x256 background map, wrapping around the bottom
+            let background_map_line = self.ly.wrapping_add(self.scy);
+
             // The row in the 32x32 tile map -> (32*8 X 32*8 pixel map)
-            let tile_map_row = background_map_line / 8;
+            let tile_map_row = (background_map_line / 8) as u16;
 
             // Line # in individual tile
-            let line_in_tile = background_map_line % 8; // Need to account for 16 size tiles mode
+            let line_in_tile = (background_map_line % 8) as u16;
 
-            for tile_map_col in 0..=19{ // Need to account for SCX later on - 18 tiles in row of viewport map
+            let background_map_base: u16 = if test_bit_u8(self.lcd_control, BG_MAP_SELECT_POS) {0x9C00 - 0x8000} else {0x9800 - 0x8000};
 
-                let background_map_base = if test_bit_u8(self.lcd_control, 3) {0x9C00} else {0x9800};
-                let tile_num_addr: u16 = background_map_base + ( (tile_map_row) as u16* 32) as u16 + (tile_map_col as u16) - 0x8000;
+            for x in 0..WIDTH{
+                // Column in the background map, wrapping around the right edge
+                let background_map_col = (x as u8).wrapping_add(self.scx);
+                let tile_map_col = (background_map_col / 8) as u16;
 
-                let tile_num = self.vram[tile_num_addr as usize];
+                let tile_num = self.vram[(background_map_base + tile_map_row * 32 + tile_map_col) as usize];
+                let tile_data_loc = self.tile_data_loc(tile_num);
 
-                let tile_data_loc = if test_bit_u8(self.lcd_control, 4){ 
-                    (0x8000 + (tile_num as u16 * 16) as u16) - 0x8000
-                }
-                else{
-                    (0x8800 + (((tile_num as i8 as i16 + 128) as u16) * 16))  - 0x8000
-                };
+                let row_byte_1 = self.vram[(tile_data_loc + line_in_tile * 2) as usize];
+                let row_byte_2 = self.vram[(tile_data_loc + line_in_tile * 2 + 1) as usize];
+                let bit = 7 - (background_map_col % 8);
+                let color_num = (((row_byte_2 >> bit) & 0x01) << 1) | ((row_byte_1 >> bit) & 0x01);
 
-                //let tile_data_loc: u16 = 0x8000 + (tile_num as u16 * 16) as u16 - 0x8000;
-            
-                let row_byte_1 = self.vram[(tile_data_loc + (line_in_tile * 2) as u16) as usize];
-                let row_byte_2 = self.vram[(tile_data_loc + (line_in_tile * 2 + 1) as u16)  as usize];
+                let color_idx = ( self.bg_pallete >> (color_num*2) ) & 0x03;
 
-                let low = tile_map_col * 8;
-                let high = low + 8;
+                self.bg_line[x] = color_num;
+                self.back_buffer[self.ly as usize * WIDTH + x] = self.bg_colors[color_idx as usize];
+            }
+        }
+        else{
+            // With the background off the line is blank and sprites always win
+            self.bg_line = [0; 160];
+            for x in 0..WIDTH{
+                self.back_buffer[self.ly as usize * WIDTH + x] = self.bg_colors[0];
+            }
+        }
+    }
 
-                let mut bit_mask = 0x80;
+    fn sprite_height(&self) -> i32{
+        if test_bit_u8(self.lcd_control, SPRITE_SIZE_BIT_POS) {16} else {8}
+    }
 
-                for i in low..high{
+    // OAM scan: the first 10 sprites in OAM order that cover the current line, then sorted so the lowest X
+    // wins ( ties go to the lower OAM index )
+    fn select_sprites(&mut self){
+        let sprite_y_size = self.sprite_height();
+        let limit = if self.sprite_limit {10} else {40};
+
+        self.sprite_count = 0;
+        for oam_idx in 0..40{
+            let y_pos = self.oam_mem[oam_idx * 4] as i32 - 16;
+            if (self.ly as i32) >= y_pos && (self.ly as i32) < y_pos + sprite_y_size{
+                self.sprite_line_data[self.sprite_count] = oam_idx as u8;
+                self.sprite_count += 1;
+                if self.sprite_count == limit{
+                    break;
+                }
+            }
+        }
+
+        let oam = &self.oam_mem;
+        self.sprite_line_data[..self.sprite_count].sort_by_key(|idx| (oam[*idx as usize * 4 + 1], *idx));
+    }
 
-                    let high_bit = (row_byte_2 & bit_mask) >> (7 - (i %8));
-                    let low_bit = (row_byte_1 & bit_mask) >> (7 - (i%8)); 
+    // Colour number of a sprite at screen column x, 0 if transparent or not covering x
+    fn sprite_pixel(&self, oam_idx: usize, x: i32) -> u8{
+        let y_pos = self.oam_mem[oam_idx * 4] as i32 - 16;
+        let x_pos = self.oam_mem[oam_idx * 4 + 1] as i32 - 8;
+        let mut tile_num = self.oam_mem[oam_idx * 4 + 2];
+        let sprite_attr = self.oam_mem[oam_idx * 4 + 3];
+        let sprite_y_size = self.sprite_height();
 
-                    let color_num = (high_bit << 1) | low_bit;
-                    
-                    let color_idx = ( self.bg_pallete >> (color_num*2) ) & 0x03;
-                    
-                    self.back_buffer[ ( (self.ly) as u16 *160 +  i)  as usize] = self.colors[color_idx as usize];
-                    
-                    bit_mask = bit_mask >> 1;
+        if x < x_pos || x >= x_pos + 8{
+            return 0;
+        }
 
-                }   
+        let mut line_in_sprite = self.ly as i32 - y_pos;
+        if test_bit_u8(sprite_attr, 6){
+            line_in_sprite = sprite_y_size - line_in_sprite - 1;
+        }
+        if sprite_y_size == 16{
+            // Bit 0 of the tile number is ignored, the bottom half uses the next tile
+            tile_num &= 0xFE;
+            if line_in_sprite >= 8{
+                tile_num |= 0x01;
+                line_in_sprite -= 8;
             }
-        } // Else {Draw color 0 ?? - TODO}
+        }
+
+        let mut col = x - x_pos;
+        if test_bit_u8(sprite_attr, 5){
+            col = 7 - col;
+        }
+
+        // Sprites always use unsigned tile numbers from 0x8000
+        let tile_data_loc = tile_num as usize * 16 + line_in_sprite as usize * 2;
+        let row_byte_1 = self.vram[tile_data_loc];
+        let row_byte_2 = self.vram[tile_data_loc + 1];
+        (((row_byte_2 >> (7 - col)) & 0x01) << 1) | ((row_byte_1 >> (7 - col)) & 0x01)
     }
 
     fn render_sprites_in_line(&mut self){
+        if !test_bit_u8(self.lcd_control, SPRITE_ENABLE_MASK){
+            return;
+        }
 
-        if test_bit_u8(self.lcd_control, SPRITE_ENABLE_MASK){
-            // For each sprite in OAM check if sprite appears in current scanline
-            for oam_idx in 0..40{
-
-                let y_pos:i32 = self.oam_mem[oam_idx*4] as i32 - 16;
-                let x_pos:i32 = self.oam_mem[oam_idx*4 + 1] as i32 - 8;
-                let mut tile_num = self.oam_mem[oam_idx*4 + 2];
-                let sprite_attr = self.oam_mem[oam_idx*4 + 3];
-                let sprite_y_size: i32 = if test_bit_u8(self.lcd_control, SPRITE_SIZE_BIT_POS){
-                    16
-                }else{
-                    8
-                };
-                let x_flip = test_bit_u8(sprite_attr, 5 );
-                let y_flip = test_bit_u8(sprite_attr, 6 ) ;
-    
-                //println!("Y_Pos: {}, sprite_y_size: {}, LY: {}", y_pos, sprite_y_size, self.ly);
-    
-                if self.ly >= (y_pos as u8) && (self.ly as i32) < y_pos + sprite_y_size{
-    
-                    //println!("Hello");
-    
-                    let mut line_in_sprite = self.ly - (y_pos as u8);
-    
-                    if y_flip{
-                        line_in_sprite = sprite_y_size as u8 - line_in_sprite - 1 ;
-                    }
-    
-                    if line_in_sprite as i32 >= sprite_y_size{
-                        panic!("Line in sprite can not be greater than sprite size: LINE: {}, SPRITE SIZE: {} ", line_in_sprite, sprite_y_size);
-                    }
+        self.select_sprites();
 
-                    if sprite_y_size == 16 {
-                        if line_in_sprite < 8{
-                            tile_num &= 0xFE;
-                        }
-                        else{
-                            tile_num |= 0x01;
-                        }
-                    }
-                    
-                    let tile_data_loc = if test_bit_u8(self.lcd_control, 4){ 
-                        (0x8000 + (tile_num as u16 * 16) as u16) - 0x8000
-                    }
-                    else{
-                        (0x8800 + (((tile_num as i8 as i16 + 128) as u16) * 16))  - 0x8000
-                    };
-    
-                    let row_byte_1 = self.vram[(tile_data_loc + (line_in_sprite * 2) as u16) as usize];
-                    let row_byte_2 = self.vram[(tile_data_loc + (line_in_sprite * 2 + 1) as u16)  as usize];
-                    let mut bit_mask = 0x80;
-    
-                    // Stepping through the 8 pixels in a sprites row
-                    for sprite_col in 0..8{
-                        //println!("X_pos: {}, sprite_col: {}", x_pos, sprite_col);
-                        if(x_pos + sprite_col) >= 0{
-                            let mut a = sprite_col;
-                            if x_flip{
-                                //println!("OLd {}", sprite_col);
-                                a = 7 - sprite_col;
-                            }
-    
-                            let high_bit = (row_byte_2 >> (7 - a )) & 0x01;
-                            let low_bit = (row_byte_1  >> (7 -  a )) & 0x01;
-                            let color_num = (high_bit << 1) | low_bit;
-                            
-                            let color_idx =  if test_bit_u8(sprite_attr, 4)
-                            {
-                                (self.obj_pallete_2 >> (color_num*2)) & 0x03
-                            }else{
-                                (self.obj_pallete_1 >> (color_num*2)) & 0x03
-                            };
-                            
-                            let color = self.colors[color_idx as usize];
-                            //println!("Drawing a sprite !");
-                            if color != WHITE  {
-                                self.back_buffer[ (self.ly as u16 * 160 + (x_pos + sprite_col) as u16) as usize ] = self.colors[color_idx as usize];
-                            }
-                        }
-                        bit_mask = bit_mask >> 1;
-                    }
-    
+        for x in 0..WIDTH{
+            // The highest priority sprite with an opaque pixel here decides, even if the BG then hides it
+            for i in 0..self.sprite_count{
+                let oam_idx = self.sprite_line_data[i] as usize;
+                let color_num = self.sprite_pixel(oam_idx, x as i32);
+                if color_num == 0{
+                    continue;
                 }
-    
+
+                let sprite_attr = self.oam_mem[oam_idx * 4 + 3];
+                // Attribute bit 7: BG colours 1 - 3 are drawn over the sprite
+                if test_bit_u8(sprite_attr, 7) && self.bg_line[x] != 0{
+                    break;
+                }
+
+                let (pallete, colors) = if test_bit_u8(sprite_attr, 4) {(self.obj_pallete_2, self.obj1_colors)} else {(self.obj_pallete_1, self.obj0_colors)};
+                let color_idx = (pallete >> (color_num * 2)) & 0x03;
+                self.back_buffer[self.ly as usize * WIDTH + x] = colors[color_idx as usize];
+                break;
             }
         }
+    }
+
+    pub fn set_sprite_limit(&mut self, enabled: bool){
+        self.sprite_limit = enabled;
+    }
+
+    pub fn set_pixel_fifo(&mut self, enabled: bool){
+        self.pixel_fifo = enabled;
+    }
 
+    pub fn set_palettes(&mut self, bg: Palette, obj0: Palette, obj1: Palette){
+        self.bg_colors = bg;
+        self.obj0_colors = obj0;
+        self.obj1_colors = obj1;
+    }
+
+    // Offset into VRAM of a BG / window tile, LCDC.4 picks unsigned tile numbers from 0x8000 or signed ones from 0x9000
+    fn tile_data_loc(&self, tile_num: u8) -> u16{
+        if (self.lcd_control & TILE_DATA_SELECT_MASK) != 0{
+            tile_num as u16 * 16
+        }
+        else{
+            (0x8800 + (((tile_num as i8 as i16 + 128) as u16) * 16)) - 0x8000
+        }
     }
 
     pub fn render_window_line(&mut self){
+        if self.ly == self.wy{
+            self.window_y_triggered = true;
+        }
 
+        let enabled = (self.lcd_control & WINDOW_DISPLAY_MASK) != 0 && test_bit_u8(self.lcd_control, BG_WIND_ENABLE_BIT_POS);
+        if !enabled || !self.window_y_triggered || self.wx > 166{
+            self.window_full_line = false;
+            return;
+        }
+
+        // WX below 7 scrolls the window's left edge off screen
+        let start_x: i32 = if self.window_full_line {0} else {self.wx as i32 - 7};
+        self.window_full_line = self.wx == 166;
+
+        let window_map_base: u16 = if (self.lcd_control & WINDOW_TILE_MAP_MASK) != 0 {0x9C00 - 0x8000} else {0x9800 - 0x8000};
+        let tile_map_row = (self.window_line / 8) as u16;
+        let line_in_tile = (self.window_line % 8) as u16;
+
+        for x in start_x.max(0)..WIDTH as i32{
+            let window_x = (x - start_x) as u16;
+            let tile_num = self.vram[(window_map_base + tile_map_row * 32 + window_x / 8) as usize];
+            let tile_data_loc = self.tile_data_loc(tile_num);
+
+            let row_byte_1 = self.vram[(tile_data_loc + line_in_tile * 2) as usize];
+            let row_byte_2 = self.vram[(tile_data_loc + line_in_tile * 2 + 1) as usize];
+            let bit = 7 - (window_x % 8);
+            let color_num = (((row_byte_2 >> bit) & 0x01) << 1) | ((row_byte_1 >> bit) & 0x01);
+
+            let color_idx = (self.bg_pallete >> (color_num * 2)) & 0x03;
+            self.bg_line[x as usize] = color_num;
+            self.back_buffer[self.ly as usize * WIDTH + x as usize] = self.bg_colors[color_idx as usize];
+        }
+
+        self.window_line += 1;
     }
 
     pub fn fill_scanline(&mut self){
+        if self.blank_frame{
+            return;
+        }
 
         // Render Background
         self.render_background_line();
@@ -270,10 +341,22 @@ impl PPU {
     }
 
     pub fn draw_frame(&mut self){
-        self.window.update_with_buffer(&self.back_buffer, 160, 144).unwrap(); 
+        self.frame_ready = true;
+    }
+
+    // The finished frame, 0RGB pixels row by row, if one completed since the last call
+    pub fn take_frame(&mut self) -> Option<&[u32]>{
+        if !self.frame_ready{
+            return None;
+        }
+        self.frame_ready = false;
+        Some(&self.back_buffer)
     }
 
     pub fn ppu_step(&mut self, m_cycles: u8){
+        if !test_bit_u8(self.lcd_control, LCD_DISPLAY_ENABLE_BIT_POS){
+            return;
+        }
         
         self.ppu_clock += (m_cycles * 4) as u16;
         match self.mode{
@@ -281,59 +364,68 @@ impl PPU {
                 if self.ppu_clock >= OAM_CYCLES{
                     self.mode = PPU_MODE::DRAW;
                     self.ppu_clock %= OAM_CYCLES;
+                    if self.pixel_fifo{
+                        self.fifo_start_line();
+                    }
                 }
-                if self.stat_interrupt == 1{
-                    self.stat_interrupt = 0;
+            },
+            PPU_MODE::DRAW if self.pixel_fifo => {
+                // ppu_clock counts the dots into mode 3, catch the FIFO up with it
+                while self.fifo.dots < self.ppu_clock{
+                    if self.fifo_tick(){
+                        self.mode = PPU_MODE::HBLANK;
+                        self.ppu_clock -= self.fifo.dots;
+                        self.hblank_cycles = (DRAW_CYCLES + HBLANK_CYCLES).saturating_sub(self.fifo.dots);
+                        break;
+                    }
                 }
-                
             },
             PPU_MODE::DRAW => {
                 if self.ppu_clock >= DRAW_CYCLES{
                     self.mode = PPU_MODE::HBLANK;
                     self.ppu_clock %=  DRAW_CYCLES;
+                    self.hblank_cycles = HBLANK_CYCLES;
 
-                    self.fill_scanline(); // Now only filling background. Need to add window and sprites later
-                    
+                    self.fill_scanline();
                 }
             },
             PPU_MODE::HBLANK => {
-                if self.ppu_clock >= HBLANK_CYCLES{
+                if self.ppu_clock >= self.hblank_cycles{
 
                     self.ly += 1; // Increment Scanline
-                    self.ppu_clock %= HBLANK_CYCLES;
+                    self.ppu_clock -= self.hblank_cycles;
                     self.mode = PPU_MODE::OAM;
 
-                    if self.ly == self.lyc {
-                        if test_bit_u8(self.lcd_stat,STAT_LYC_BIT_POS){
-                            self.stat_interrupt = 1;
-                        } 
-                        self.lcd_stat |= 0x01 << STAT_COINCIDENCE_BIT_POS;        
-                    }
-
-                    if self.ly == 143 {
+                    if self.ly == 144 {
 
-                        // Update Window's buffer with new data                            
+                        // Let the frontend know the frame is complete
                         self.draw_frame();
+                        self.blank_frame = false;
                         self.mode = PPU_MODE::VBLANK;
-
-                        if test_bit_u8(self.lcd_stat, LCD_DISPLAY_ENABLE_BIT_POS){
-                            self.vblank_interrupt = 1;
-                        }
+                        self.vblank_interrupt = 1;
 
                     }
                 }
                 
             },
             PPU_MODE::VBLANK => {
-                self.vblank_interrupt = 0;
+                // LY reads 153 only for the first M-cycle of line 153, then 0 ( and LYC compares against 0 ) for the rest of it
+                if self.ly == 153 && self.ppu_clock >= 4{
+                    self.ly = 0;
+                }
+
                 if self.ppu_clock >= VBANK_CYCLES{
                     self.ppu_clock %= VBANK_CYCLES;
-                    self.ly += 1;
 
-                    if self.ly > 153 {
-                        self.ly = 0;
+                    if self.ly == 0 {
+                        self.window_line = 0;
+                        self.window_y_triggered = false;
+                        self.window_full_line = false;
                         self.mode = PPU_MODE::OAM;
                     }
+                    else{
+                        self.ly += 1;
+                    }
 
                 }
             }
@@ -341,20 +433,88 @@ impl PPU {
 
         }
 
+        self.update_stat();
+    }
+
+    // The PPU stops with LY at 0 and STAT reporting mode 0, the screen goes blank
+    fn lcd_off(&mut self){
+        self.ly = 0;
+        self.ppu_clock = 0;
+        self.mode = PPU_MODE::HBLANK;
+        self.lcd_stat &= !STAT_MODE_MASK;
+        self.stat_line = false;
+        for pixel in self.back_buffer.iter_mut(){
+            *pixel = self.bg_colors[0];
+        }
+        self.draw_frame();
+    }
+
+    // Restarts from the top of the frame, nothing is drawn until the next one
+    fn lcd_on(&mut self){
+        self.ly = 0;
+        self.ppu_clock = 0;
+        self.mode = PPU_MODE::OAM;
+        self.window_line = 0;
+        self.window_y_triggered = false;
+        self.window_full_line = false;
+        self.blank_frame = true;
+        self.update_stat();
+    }
+
+    // Refreshes the mode and coincidence bits and requests a STAT interrupt when any enabled source goes high.
+    // While one source holds the line high, others becoming true do not request another interrupt ( STAT blocking )
+    fn update_stat(&mut self){
+        self.lcd_stat = (self.lcd_stat & !(STAT_MODE_MASK | (1 << STAT_COINCIDENCE_BIT_POS))) | self.mode as u8;
+        if self.ly == self.lyc{
+            self.lcd_stat |= 1 << STAT_COINCIDENCE_BIT_POS;
+        }
+
+        let stat_line = (test_bit_u8(self.lcd_stat, STAT_LYC_BIT_POS) && self.ly == self.lyc)
+            || (test_bit_u8(self.lcd_stat, STAT_HBLANK_BIT_POS) && self.mode == PPU_MODE::HBLANK)
+            || (test_bit_u8(self.lcd_stat, STAT_VBLANK_BIT_POS) && self.mode == PPU_MODE::VBLANK)
+            || (test_bit_u8(self.lcd_stat, STAT_OAM_BIT_POS) && self.mode == PPU_MODE::OAM);
+
+        if stat_line && !self.stat_line{
+            self.stat_interrupt = 1;
+        }
+        self.stat_line = stat_line;
     }
 
     pub fn write_byte(&mut self, loc: u16, val: u8){
         match loc{
             0xFE00..=0xFE9F => self.oam_mem[(loc - 0xFE00) as usize] = val,
-            0xFF40 => { /*println!("Writing to lcdc: {:x?}", val); */self.lcd_control = val},
-            0xFF41 => self.lcd_stat = self.lcd_stat | (val & 0xF4), // Bits 0 through 2 are read only and set only by the PPU
+            0xFF40 => {
+                let was_on = test_bit_u8(self.lcd_control, LCD_DISPLAY_ENABLE_BIT_POS);
+                self.lcd_control = val;
+                let is_on = test_bit_u8(self.lcd_control, LCD_DISPLAY_ENABLE_BIT_POS);
+                if was_on && !is_on{
+                    self.lcd_off();
+                }
+                else if !was_on && is_on{
+                    self.lcd_on();
+                }
+            }
+            0xFF41 => {
+                // Bits 0 through 2 are read only and set only by the PPU
+                self.lcd_stat = (self.lcd_stat & !STAT_WRITE_MASK) | (val & STAT_WRITE_MASK);
+                self.update_stat();
+            }
             0xFF42 => self.scy = val,
             0xFF43 => self.scx = val,
             0xFF44 => self.ly = val,
-            0xFF45 => self.lyc = val,
+            0xFF45 => {
+                self.lyc = val;
+                self.update_stat();
+            }
             0xFF47 => self.bg_pallete = val,
             0xFF48 => {self.obj_pallete_1 = val; /*println!("Changing obj pallete 1 ")*/ },
             0xFF49 => {self.obj_pallete_2 = val; /*println!("Changing obj pallete 2")*/},
+            0xFF4A => self.wy = val,
+            0xFF4B => self.wx = val,
+            0xFF0F => {
+                self.vblank_interrupt = val & 0x01;
+                self.stat_interrupt = (val >> 1) & 0x01;
+            }
             _ => self.vram[(loc - 0x8000) as usize] = val
         }
         
@@ -364,7 +524,7 @@ impl PPU {
         match loc{
             0xFE00..=0xFE9F => self.oam_mem[(loc - 0xFE00) as usize],
             0xFF40 => self.lcd_control,
-            0xFF41 => self.lcd_stat,
+            0xFF41 => self.lcd_stat | 0x80,
             0xFF42 => self.scy,
             0xFF43 => self.scx,
             0xFF44 => self.ly,
@@ -372,6 +532,8 @@ impl PPU {
             0xFF47 => self.bg_pallete,
             0xFF48 => self.obj_pallete_1,
             0xFF49 => self.obj_pallete_2,
+            0xFF4A => self.wy,
+            0xFF4B => self.wx,
             _ => self.vram[(loc - 0x8000) as usize] 
         }
     }
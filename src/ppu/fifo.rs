@@ -0,0 +1,361 @@
+use std::collections::VecDeque;
+
+use super::*;
+
+// Pixel FIFO renderer. Instead of drawing a whole line when mode 3 ends, the fetchers run one dot at a time so
+// registers written during mode 3 take effect mid line and mode 3 gets longer for SCX, the window and sprites.
+
+#[derive(Clone, Copy, PartialEq)]
+enum FetchStep{
+    TileNum,
+    DataLow,
+    DataHigh,
+    Push
+}
+
+#[derive(Clone, Copy)]
+struct ObjPixel{
+    color: u8,          // 0 is transparent
+    pallete: bool,      // OBP1 if set
+    behind_bg: bool     // Attribute bit 7
+}
+
+pub struct PixelFifo{
+    bg_fifo: VecDeque<u8>,   // BG / window colour numbers
+    obj_fifo: VecDeque<ObjPixel>,
+    step: FetchStep,
+    step_dots: u8,           // Every fetch step takes 2 dots
+    fetcher_x: u8,           // Tile column the fetcher is on, relative to SCX / the window's left edge
+    tile_num: u8,
+    data_low: u8,
+    data_high: u8,
+    lx: u8,                  // Next screen column to output
+    discard: u8,             // Pixels still to drop, SCX % 8 at the start of the line or the hidden part of the window
+    in_window: bool,
+    delay: u8,               // Dots before the first fetch starts
+    next_sprite: usize,      // Index into sprite_line_data of the next sprite to fetch
+    sprite_dots: u8,         // Dots spent fetching the current sprite
+    pub dots: u16            // Dots into mode 3
+}
+
+impl PixelFifo{
+
+    pub fn new() -> PixelFifo{
+        PixelFifo{
+            bg_fifo: VecDeque::with_capacity(16),
+            obj_fifo: VecDeque::with_capacity(8),
+            step: FetchStep::TileNum,
+            step_dots: 0,
+            fetcher_x: 0,
+            tile_num: 0,
+            data_low: 0,
+            data_high: 0,
+            lx: 0,
+            discard: 0,
+            in_window: false,
+            delay: 0,
+            next_sprite: 0,
+            sprite_dots: 0,
+            dots: 0
+        }
+    }
+
+    fn restart_fetch(&mut self){
+        self.bg_fifo.clear();
+        self.step = FetchStep::TileNum;
+        self.step_dots = 0;
+        self.fetcher_x = 0;
+    }
+}
+
+impl PPU{
+
+    // Called when mode 3 starts
+    pub(super) fn fifo_start_line(&mut self){
+        if self.ly == self.wy{
+            self.window_y_triggered = true;
+        }
+        self.select_sprites();
+
+        self.fifo.restart_fetch();
+        self.fifo.obj_fifo.clear();
+        self.fifo.lx = 0;
+        self.fifo.discard = self.scx & 0x07;
+        self.fifo.in_window = false;
+        self.fifo.delay = 6; // The first tile fetch of every line is thrown away
+        self.fifo.next_sprite = 0;
+        self.fifo.sprite_dots = 0;
+        self.fifo.dots = 0;
+    }
+
+    // Advances mode 3 by one dot, returns true once all 160 pixels of the line are out
+    pub(super) fn fifo_tick(&mut self) -> bool{
+        self.fifo.dots += 1;
+        if self.fifo.delay > 0{
+            self.fifo.delay -= 1;
+            return false;
+        }
+
+        if self.fifo.discard == 0 && self.start_window(){
+            return false;
+        }
+
+        // A sprite at the current column stops the pixel output: the BG fetch in progress is finished first,
+        // then the sprite takes another 6 dots. Sprites past the hardware limit of 10 ( with the limit turned off )
+        // are fetched for free so the line still fits in 456 dots
+        if self.sprite_pending() && self.fifo.next_sprite >= 10{
+            self.fetch_sprite();
+            return false;
+        }
+        if self.sprite_pending(){
+            if self.fifo.step != FetchStep::Push || self.fifo.bg_fifo.is_empty(){
+                self.fetcher_step();
+                return false;
+            }
+            self.fifo.sprite_dots += 1;
+            if self.fifo.sprite_dots == 6{
+                self.fifo.sprite_dots = 0;
+                self.fetch_sprite();
+            }
+            return false;
+        }
+
+        self.fetcher_step();
+        self.push_pixel();
+        self.fifo.lx as usize == WIDTH
+    }
+
+    fn start_window(&mut self) -> bool{
+        let enabled = (self.lcd_control & WINDOW_DISPLAY_MASK) != 0 && test_bit_u8(self.lcd_control, BG_WIND_ENABLE_BIT_POS);
+        if self.fifo.in_window || !enabled || !self.window_y_triggered || self.wx > 166{
+            return false;
+        }
+        // After a window line with WX=166 the window covers the whole next line
+        if !self.window_full_line && (self.fifo.lx as u16 + 7) < self.wx as u16{
+            return false;
+        }
+
+        self.fifo.in_window = true;
+        self.fifo.restart_fetch();
+        // WX below 7 scrolls the window's left edge off screen
+        if self.fifo.lx == 0 && self.wx < 7 && !self.window_full_line{
+            self.fifo.discard = 7 - self.wx;
+        }
+        true
+    }
+
+    fn sprite_pending(&self) -> bool{
+        if self.fifo.discard > 0 || self.fifo.next_sprite >= self.sprite_count{
+            return false;
+        }
+        if !test_bit_u8(self.lcd_control, SPRITE_ENABLE_MASK){
+            return false;
+        }
+        let oam_idx = self.sprite_line_data[self.fifo.next_sprite] as usize;
+        let x_pos = self.oam_mem[oam_idx * 4 + 1] as i32 - 8;
+        x_pos <= self.fifo.lx as i32
+    }
+
+    fn fetch_sprite(&mut self){
+        let oam_idx = self.sprite_line_data[self.fifo.next_sprite] as usize;
+        self.fifo.next_sprite += 1;
+
+        let x_pos = self.oam_mem[oam_idx * 4 + 1] as i32 - 8;
+        let sprite_attr = self.oam_mem[oam_idx * 4 + 3];
+        let lx = self.fifo.lx as i32;
+
+        while self.fifo.obj_fifo.len() < 8{
+            self.fifo.obj_fifo.push_back(ObjPixel{ color: 0, pallete: false, behind_bg: false });
+        }
+
+        // Pixels already in the FIFO belong to sprites with a higher priority, only transparent ones get replaced
+        for i in 0..8{
+            let x = lx + i as i32;
+            if x >= x_pos + 8{
+                break;
+            }
+            let color = self.sprite_pixel(oam_idx, x);
+            if self.fifo.obj_fifo[i].color == 0 && color != 0{
+                self.fifo.obj_fifo[i] = ObjPixel{
+                    color: color,
+                    pallete: test_bit_u8(sprite_attr, 4),
+                    behind_bg: test_bit_u8(sprite_attr, 7)
+                };
+            }
+        }
+    }
+
+    fn fetcher_step(&mut self){
+        if self.fifo.step == FetchStep::Push{
+            // Only pushes into an empty FIFO
+            if !self.fifo.bg_fifo.is_empty(){
+                return;
+            }
+            for bit in (0..8).rev(){
+                let color_num = (((self.fifo.data_high >> bit) & 0x01) << 1) | ((self.fifo.data_low >> bit) & 0x01);
+                self.fifo.bg_fifo.push_back(color_num);
+            }
+            self.fifo.fetcher_x = self.fifo.fetcher_x.wrapping_add(1);
+            self.fifo.step = FetchStep::TileNum;
+            return;
+        }
+
+        self.fifo.step_dots += 1;
+        if self.fifo.step_dots < 2{
+            return;
+        }
+        self.fifo.step_dots = 0;
+
+        match self.fifo.step{
+            FetchStep::TileNum => {
+                self.fifo.tile_num = self.vram[self.fetcher_map_loc() as usize];
+                self.fifo.step = FetchStep::DataLow;
+            }
+            FetchStep::DataLow => {
+                self.fifo.data_low = self.vram[self.fetcher_data_loc() as usize];
+                self.fifo.step = FetchStep::DataHigh;
+            }
+            FetchStep::DataHigh => {
+                self.fifo.data_high = self.vram[self.fetcher_data_loc() as usize + 1];
+                self.fifo.step = FetchStep::Push;
+            }
+            FetchStep::Push => {}
+        }
+    }
+
+    // Line of the 256x256 map the fetcher is reading
+    fn fetcher_map_line(&self) -> u8{
+        if self.fifo.in_window {self.window_line} else {self.ly.wrapping_add(self.scy)}
+    }
+
+    fn fetcher_map_loc(&self) -> u16{
+        let (map_base, col) = if self.fifo.in_window{
+            let base: u16 = if (self.lcd_control & WINDOW_TILE_MAP_MASK) != 0 {0x9C00 - 0x8000} else {0x9800 - 0x8000};
+            (base, self.fifo.fetcher_x as u16 & 0x1F)
+        }
+        else{
+            let base: u16 = if test_bit_u8(self.lcd_control, BG_MAP_SELECT_POS) {0x9C00 - 0x8000} else {0x9800 - 0x8000};
+            (base, ((self.scx >> 3) as u16 + self.fifo.fetcher_x as u16) & 0x1F)
+        };
+        map_base + (self.fetcher_map_line() / 8) as u16 * 32 + col
+    }
+
+    fn fetcher_data_loc(&self) -> u16{
+        self.tile_data_loc(self.fifo.tile_num) + (self.fetcher_map_line() % 8) as u16 * 2
+    }
+
+    fn push_pixel(&mut self){
+        let bg_color = match self.fifo.bg_fifo.pop_front(){
+            Some(color) => color,
+            None => return
+        };
+
+        if self.fifo.discard > 0{
+            self.fifo.discard -= 1;
+            return;
+        }
+
+        let bg_color = if test_bit_u8(self.lcd_control, BG_WIND_ENABLE_BIT_POS) {bg_color} else {0};
+        let mut color = self.bg_colors[((self.bg_pallete >> (bg_color * 2)) & 0x03) as usize];
+
+        if let Some(obj) = self.fifo.obj_fifo.pop_front(){
+            let visible = obj.color != 0 && test_bit_u8(self.lcd_control, SPRITE_ENABLE_MASK);
+            if visible && !(obj.behind_bg && bg_color != 0){
+                let (pallete, colors) = if obj.pallete {(self.obj_pallete_2, self.obj1_colors)} else {(self.obj_pallete_1, self.obj0_colors)};
+                color = colors[((pallete >> (obj.color * 2)) & 0x03) as usize];
+            }
+        }
+
+        if !self.blank_frame{
+            self.back_buffer[self.ly as usize * WIDTH + self.fifo.lx as usize] = color;
+        }
+        self.fifo.lx += 1;
+
+        if self.fifo.lx as usize == WIDTH{
+            if self.fifo.in_window{
+                self.window_line += 1;
+            }
+            self.window_full_line = self.fifo.in_window && self.wx == 166;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const CYCLES_PER_FRAME: u32 = 17556; // M-cycles
+
+    // M-cycles between two completed frames
+    fn frame_length(ppu: &mut PPU) -> u32{
+        let mut frames = 0;
+        let mut cycles = 0;
+        while frames < 2{
+            ppu.ppu_step(1);
+            if frames == 1{
+                cycles += 1;
+            }
+            if ppu.take_frame().is_some(){
+                frames += 1;
+            }
+            assert!(cycles <= CYCLES_PER_FRAME * 2, "PPU stalled");
+        }
+        cycles
+    }
+
+    #[test]
+    fn forty_sprites_on_one_line_without_limit(){
+        let mut ppu = PPU::new();
+        ppu.set_pixel_fifo(true);
+        ppu.set_sprite_limit(false);
+        ppu.write_byte(0xFF40, 0x93);
+        for i in 0..40u16{
+            ppu.write_byte(0xFE00 + i * 4, 16 + 20);              // Y, all on lines 20 - 27
+            ppu.write_byte(0xFE00 + i * 4 + 1, 8 + (i as u8) * 4); // X
+        }
+
+        assert_eq!(frame_length(&mut ppu), CYCLES_PER_FRAME);
+        assert!(ppu.hblank_cycles <= HBLANK_CYCLES);
+    }
+
+    // Last complete frame of a scene with a different tile in each map column
+    fn render(pixel_fifo: bool, scx: u8, scy: u8, wx: u8, wy: u8, lcdc: u8) -> Vec<u32>{
+        let mut ppu = PPU::new();
+        ppu.set_pixel_fifo(pixel_fifo);
+        for tile in 0..3u16{
+            for row in 0..16u16{
+                ppu.write_byte(0x8000 + tile * 16 + row, (0x5A_u16.rotate_left((tile * 3 + row) as u32) & 0xFF) as u8);
+            }
+        }
+        for i in 0..0x800u16{
+            ppu.write_byte(0x9800 + i, (i % 3) as u8);
+        }
+        ppu.write_byte(0xFF42, scy);
+        ppu.write_byte(0xFF43, scx);
+        ppu.write_byte(0xFF4A, wy);
+        ppu.write_byte(0xFF4B, wx);
+        ppu.write_byte(0xFF40, lcdc);
+
+        let mut frame = Vec::new();
+        let mut frames = 0;
+        while frames < 2{
+            ppu.ppu_step(1);
+            if let Some(buffer) = ppu.take_frame(){
+                frame = buffer.to_vec();
+                frames += 1;
+            }
+        }
+        frame
+    }
+
+    #[test]
+    fn window_at_wx_166_matches_scanline_renderer(){
+        for &(scx, scy, wy) in [(1, 1, 10), (0, 0, 0), (5, 3, 100)].iter(){
+            let scanline = render(false, scx, scy, 166, wy, 0xB3);
+            let fifo = render(true, scx, scy, 166, wy, 0xB3);
+
+            let first_diff = scanline.iter().zip(fifo.iter()).position(|(a, b)| a != b);
+            assert_eq!(first_diff.map(|i| (i % WIDTH, i / WIDTH)), None, "SCX={} SCY={} WY={}", scx, scy, wy);
+        }
+    }
+}
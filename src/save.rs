@@ -0,0 +1,36 @@
+use std::{fs, io::{self, Write}, path::{Path, PathBuf}};
+
+// <rom>.sav next to the ROM, or inside save_dir when one is given
+pub fn save_path(rom_path: &str, save_dir: Option<&str>) -> PathBuf{
+    let sav = Path::new(rom_path).with_extension("sav");
+    match save_dir{
+        Some(dir) => Path::new(dir).join(sav.file_name().unwrap_or_default()),
+        None => sav
+    }
+}
+
+pub fn load(path: &Path) -> Option<Vec<u8>>{
+    match fs::read(path){
+        Ok(data) => Some(data),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => {
+            println!("Could not read save file {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+// Writes to a temporary file first and renames it over the old save, so a crash mid write leaves the old save intact
+pub fn write(path: &Path, data: &[u8]) -> io::Result<()>{
+    if let Some(dir) = path.parent(){
+        if !dir.as_os_str().is_empty(){
+            fs::create_dir_all(dir)?;
+        }
+    }
+
+    let tmp_path = path.with_extension("sav.tmp");
+    let mut tmp = fs::File::create(&tmp_path)?;
+    tmp.write_all(data)?;
+    tmp.sync_all()?;
+    fs::rename(&tmp_path, path)
+}
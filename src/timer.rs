@@ -1,49 +1,66 @@
-
-use core::time;
-
-use crate::mmu::MMU;
-
+// DIV and TIMA are both driven by one 16 bit counter that goes up every T-cycle. DIV is its upper byte,
+// TIMA ticks on the falling edge of the counter bit selected by TAC ( ANDed with the enable bit ), so
+// resetting DIV or changing TAC can tick TIMA too.
 pub struct Timer{
-    div_helper: u16, // Helper to ensure  that the div register gets incremented every 255 cycles.
-    time_helper: u16,
-    div: u8,  // 0xFF04 - Divider Register
+    counter: u16, // Internal system counter, DIV ( 0xFF04 ) is the upper 8 bits
     tima: u8, // 0xFF05 - Timer Counter
     tma: u8,  // 0xFF06 - Timer Modulo
     tac: u8,  // 0xFF07 - Timer Control
+    overflow_pending: bool, // TIMA overflowed last M-cycle, it reads 0 until TMA gets loaded in the next one
+    reloading: bool,        // TMA is being loaded into TIMA this M-cycle
     pub interrupt: u8 // 1 - true, 0 - false
 }
 
+// Counter bit watched for each TAC clock select: 4096, 262144, 65536 and 16384 Hz
+const TAC_BITS: [u16; 4] = [1 << 9, 1 << 3, 1 << 5, 1 << 7];
+
 impl Timer{
 
     pub fn new() -> Timer{
-        let mut timer = Timer{
-            div_helper: 0,
-            time_helper:0,
-            div: 0,
+        Timer{
+            counter: 0xABCC, // DIV is 0xAB when the DMG boot ROM hands over
             tima: 0,
             tma: 0,
             tac: 0,
+            overflow_pending: false,
+            reloading: false,
             interrupt: 0
-        };
-        timer
+        }
     }
 
     pub fn read_byte(&self, loc: u16) -> u8{
         match loc{
-            0xFF04 => self.div,
+            0xFF04 => (self.counter >> 8) as u8,
             0xFF05 => self.tima,
             0xFF06 => self.tma,
-            0xFF07 => self.tac,
+            0xFF07 => self.tac | 0xF8,
             _ => { panic!("Mem not in timer: read")  }
-        }   
+        }
     }
 
-pub fn write_byte(&mut self, loc: u16, val: u8){
+    pub fn write_byte(&mut self, loc: u16, val: u8){
         match loc{
-            0xFF04 => { self.div = 0x00; self.div_helper = 0; self.time_helper = 0},
-            0xFF05 => self.tima = val,
-            0xFF06 => self.tma = val,
-            0xFF07 => {self.tac = val; },
+            0xFF04 => self.set_counter(0),
+            0xFF05 => {
+                // Writing during the overflow delay cancels the reload, writing while it happens is ignored
+                if !self.reloading{
+                    self.tima = val;
+                    self.overflow_pending = false;
+                }
+            }
+            0xFF06 => {
+                self.tma = val;
+                if self.reloading{
+                    self.tima = val;
+                }
+            }
+            0xFF07 => {
+                let old_bit = self.timer_bit();
+                self.tac = val & 0x07;
+                if old_bit && !self.timer_bit(){
+                    self.increment_tima();
+                }
+            }
             0xFF0F => {
                 if (val & 0x04) > 0 {
                     self.interrupt = 1;
@@ -53,42 +70,135 @@ pub fn write_byte(&mut self, loc: u16, val: u8){
                 }
             }
             _ => {panic! ( "Mem not in timer: write")}
-        }   
+        }
     }
 
-pub fn step_cycle(&mut self, m_cycles: u8){
-    
-    self.div_helper += (m_cycles * 4  ) as u16; 
-    if self.div_helper > 255 {
-        self.div_helper -= 255;
-        self.div = self.div.wrapping_add(1);
+    pub fn step_cycle(&mut self, m_cycles: u8){
+        for _ in 0..m_cycles{
+            self.reloading = false;
+            if self.overflow_pending{
+                self.overflow_pending = false;
+                self.tima = self.tma;
+                self.interrupt = 1;
+                self.reloading = true;
+            }
+            self.set_counter(self.counter.wrapping_add(4));
+        }
     }
 
-    if ((self.tac >> 2) & 0x01) != 0{ // If timer is enabled
-
-        self.time_helper = self.time_helper.wrapping_add((m_cycles * 4) as u16);
+    // Input of the falling edge detector
+    fn timer_bit(&self) -> bool{
+        (self.tac & 0x04) != 0 && (self.counter & TAC_BITS[(self.tac & 0x03) as usize]) != 0
+    }
 
-        let mut freq:u32 = 4096;
-        if (self.tac & 3) == 1{
-            freq = 262144;
-        }
-        else if(self.tac & 3) == 2{
-            freq = 65536;
-        }
-        else if (self.tac & 3) == 3{
-            freq = 16384;
+    fn set_counter(&mut self, val: u16){
+        let old_bit = self.timer_bit();
+        self.counter = val;
+        if old_bit && !self.timer_bit(){
+            self.increment_tima();
         }
+    }
 
-        while self.time_helper >= (4194304 / freq) as u16 {
-            self.tima = self.tima.wrapping_add(1);
-            if self.tima == 0{
-                self.interrupt = 1;
-                self.tima = self.tma;
-            }
-            self.time_helper -=  (4194304 / freq) as u16 ;
+    fn increment_tima(&mut self){
+        self.tima = self.tima.wrapping_add(1);
+        if self.tima == 0{
+            self.overflow_pending = true;
         }
-
     }
 }
 
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    // Counter starts at 0xABCC, so bit 3 ( TAC clock select 1 ) is set
+    fn timer_with_tac(tac: u8) -> Timer{
+        let mut timer = Timer::new();
+        timer.write_byte(0xFF07, tac);
+        timer
+    }
+
+    #[test]
+    fn div_write_ticks_tima_on_falling_edge(){
+        let mut timer = timer_with_tac(0x05);
+        timer.write_byte(0xFF04, 0x12);
+
+        assert_eq!(timer.read_byte(0xFF04), 0);
+        assert_eq!(timer.read_byte(0xFF05), 1);
+    }
+
+    #[test]
+    fn div_write_with_bit_low_does_not_tick(){
+        let mut timer = timer_with_tac(0x05);
+        timer.write_byte(0xFF04, 0);
+        timer.write_byte(0xFF04, 0);
+
+        assert_eq!(timer.read_byte(0xFF05), 1);
+    }
+
+    #[test]
+    fn tac_change_glitch(){
+        let mut timer = timer_with_tac(0x05);
+        // Disabling the timer while the selected bit is high is a falling edge too
+        timer.write_byte(0xFF07, 0x01);
+        assert_eq!(timer.read_byte(0xFF05), 1);
+
+        // Switching to a clock select whose bit is low as well
+        let mut timer = timer_with_tac(0x05);
+        timer.write_byte(0xFF07, 0x06);
+        assert_eq!(timer.read_byte(0xFF05), 1);
+    }
+
+    #[test]
+    fn ticks_every_16_cycles_at_262144_hz(){
+        let mut timer = timer_with_tac(0x05);
+        timer.write_byte(0xFF04, 0);
+        timer.write_byte(0xFF05, 0);
+        timer.step_cycle(16);
+
+        assert_eq!(timer.read_byte(0xFF05), 4);
+    }
+
+    #[test]
+    fn overflow_reloads_tma_one_cycle_later(){
+        let mut timer = timer_with_tac(0x05);
+        timer.write_byte(0xFF06, 0x42);
+        timer.write_byte(0xFF05, 0xFF);
+        timer.write_byte(0xFF04, 0);
+
+        // TIMA reads 0 for one M-cycle before TMA is loaded and the interrupt is requested
+        assert_eq!(timer.read_byte(0xFF05), 0);
+        assert_eq!(timer.interrupt, 0);
+
+        timer.step_cycle(1);
+        assert_eq!(timer.read_byte(0xFF05), 0x42);
+        assert_eq!(timer.interrupt, 1);
+    }
+
+    #[test]
+    fn tima_write_during_overflow_delay_cancels_reload(){
+        let mut timer = timer_with_tac(0x05);
+        timer.write_byte(0xFF06, 0x42);
+        timer.write_byte(0xFF05, 0xFF);
+        timer.write_byte(0xFF04, 0);
+        timer.write_byte(0xFF05, 0x10);
+
+        timer.step_cycle(1);
+        assert_eq!(timer.read_byte(0xFF05), 0x10);
+        assert_eq!(timer.interrupt, 0);
+    }
+
+    #[test]
+    fn tima_write_during_reload_is_ignored(){
+        let mut timer = timer_with_tac(0x05);
+        timer.write_byte(0xFF06, 0x42);
+        timer.write_byte(0xFF05, 0xFF);
+        timer.write_byte(0xFF04, 0);
+        timer.step_cycle(1);
+
+        timer.write_byte(0xFF05, 0x10);
+        assert_eq!(timer.read_byte(0xFF05), 0x42);
+        timer.write_byte(0xFF06, 0x20);
+        assert_eq!(timer.read_byte(0xFF05), 0x20);
+    }
+}
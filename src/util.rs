@@ -6,4 +6,17 @@ pub fn test_bit_u8(val: u8, bit: u8) -> bool{
 
 pub fn set_bit_u8(val : &mut u8, bit: u8){
     *val |= 0x01 << bit
-}
\ No newline at end of file
+}
+
+// CRC-32 ( IEEE ), as used by zip, UPS and BPS
+pub fn crc32(data: &[u8]) -> u32{
+    let mut crc: u32 = 0xFFFFFFFF;
+    for byte in data{
+        crc ^= *byte as u32;
+        for _ in 0..8{
+            let mask = (!(crc & 1)).wrapping_add(1);
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+    !crc
+}